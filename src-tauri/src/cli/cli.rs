@@ -1,17 +1,40 @@
-use std::{collections::HashSet, fs, io, path::PathBuf};
+use std::{
+    collections::HashSet,
+    fs,
+    io::{self, IsTerminal},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
 use clap::{Parser, Subcommand};
 use log::error;
 
 use crate::{
     cli::handlers::{
+        batch::{batch_status, submit_batches},
         config::{current_config, edit_config, view_prompt_file},
+        doctor::doctor,
+        history::{history, undo},
         log::{error_logs, info_logs},
+        plan::apply_plan,
         status::{daemon, restart_daemon, start_daemon, status_daemon, stop_daemon},
+        usage::usage,
     },
+    cli::picker::TerminalPicker,
     config::config::Config,
-    manager::{ai::OpenAI, image::SSManager},
-    utils::ss::get_screenshot_dir,
+    manager::{
+        batch::BatchJob,
+        cancel::CancelToken,
+        error::ProviderError,
+        image::{CollisionStrategy, Renamed, SSManager},
+        ledger::{Ledger, BATCH_RATE},
+        pack::Packer,
+        plan::Plan,
+        profile,
+        provider::{self, Suggestion},
+        retry::RetryPolicy,
+    },
+    utils::{ss::get_screenshot_dir, time},
 };
 
 #[derive(Parser, Debug)]
@@ -30,14 +53,76 @@ pub enum Commands {
     Status,
 
     // config handlers
-    CurrentConfig,
+    CurrentConfig {
+        /// show the profile this file would be named with
+        file_path: Option<String>,
+    },
     ViewPromptFile,
     EditConfig,
 
     // utils
-    ProcessExistingScreenshots,
+    ProcessExistingScreenshots {
+        /// ask the provider again even for images it has already named
+        #[arg(long)]
+        no_cache: bool,
+        /// submit the images to the OpenAI Batch API at half the price,
+        /// results are applied by `peeksy batch-status`
+        #[arg(long)]
+        batch: bool,
+        /// name this many images with one request, overrides the config
+        #[arg(long)]
+        images_per_request: Option<u32>,
+        /// work out the renames without touching any file
+        #[arg(long, conflicts_with = "batch")]
+        dry_run: bool,
+        /// write the dry run's plan to this json file for `peeksy apply`
+        #[arg(long, requires = "dry_run")]
+        save_plan: Option<String>,
+    },
+    /// check submitted batches and apply the renames of finished ones
+    BatchStatus {
+        /// keep polling until every batch has finished
+        #[arg(long)]
+        wait: bool,
+    },
     Rename {
         file_path: String,
+        /// ask the provider again even for images it has already named
+        #[arg(long)]
+        no_cache: bool,
+        /// number of names to choose from
+        #[arg(long)]
+        candidates: Option<u32>,
+        /// rename to this name instead of asking the provider
+        #[arg(long)]
+        name: Option<String>,
+        /// description to embed with a name given by --name
+        #[arg(long, requires = "name")]
+        description: Option<String>,
+        /// tag to embed with a name given by --name, can be repeated
+        #[arg(long = "tag", requires = "name")]
+        tags: Vec<String>,
+        /// use this profile instead of the one the rules pick
+        #[arg(long)]
+        profile: Option<String>,
+        /// work out the rename without touching the file
+        #[arg(long)]
+        dry_run: bool,
+        /// write the dry run's plan to this json file for `peeksy apply`
+        #[arg(long, requires = "dry_run")]
+        save_plan: Option<String>,
+    },
+    /// carry out the renames of a saved plan without asking the provider
+    Apply {
+        plan_path: String,
+    },
+    /// print candidate names as json without renaming
+    Suggest {
+        file_path: String,
+        #[arg(long)]
+        no_cache: bool,
+        #[arg(long)]
+        candidates: Option<u32>,
     },
     #[command(name = "daemon")]
     Daemon,
@@ -45,6 +130,41 @@ pub enum Commands {
     // log handlers
     InfoLogs,
     ErrorLogs,
+
+    // usage handlers
+    Usage,
+
+    // journal handlers
+    /// list past renames
+    History {
+        /// only renames on or after this day, 2025-01-31
+        #[arg(long, value_parser = time::parse_date)]
+        since: Option<String>,
+        /// only renames on or before this day
+        #[arg(long, value_parser = time::parse_date)]
+        until: Option<String>,
+        /// only renames in this directory
+        #[arg(long)]
+        dir: Option<String>,
+        /// show at most this many renames, newest last
+        #[arg(long, default_value_t = 50)]
+        limit: usize,
+    },
+    /// move renamed files back to their old names
+    Undo {
+        /// undo the last N renames
+        #[arg(long, conflicts_with_all = ["id", "run"])]
+        last: Option<usize>,
+        /// undo the rename with this id, see `peeksy history`
+        #[arg(long, conflicts_with = "run")]
+        id: Option<String>,
+        /// undo every rename of a bulk or batch run
+        #[arg(long)]
+        run: Option<String>,
+    },
+
+    /// check that the naming providers are reachable
+    Doctor,
 }
 
 impl Args {
@@ -55,8 +175,21 @@ impl Args {
             Commands::InfoLogs => info_logs().await,
             Commands::ErrorLogs => error_logs().await,
 
+            // usage handlers
+            Commands::Usage => usage().await,
+
+            // journal handlers
+            Commands::History {
+                since,
+                until,
+                dir,
+                limit,
+            } => history(since.as_deref(), until.as_deref(), dir.as_deref(), *limit).await,
+            Commands::Undo { last, id, run } => undo(*last, id.as_deref(), run.as_deref()).await,
+            Commands::Doctor => doctor().await,
+
             // config handlers
-            Commands::CurrentConfig => current_config().await,
+            Commands::CurrentConfig { file_path } => current_config(file_path.as_deref()).await,
             Commands::ViewPromptFile => view_prompt_file().await,
             Commands::EditConfig => edit_config().await,
 
@@ -68,14 +201,70 @@ impl Args {
             Commands::Daemon => daemon().await,
 
             // utils handlers
-            Commands::Rename { file_path } => rename_file(file_path).await,
-            Commands::ProcessExistingScreenshots => process_existing_screenshots().await,
+            Commands::Rename {
+                file_path,
+                no_cache,
+                candidates,
+                name,
+                description,
+                tags,
+                profile,
+                dry_run,
+                save_plan,
+            } => {
+                let chosen = name.as_ref().map(|name| Suggestion {
+                    name: name.clone(),
+                    description: description.clone(),
+                    tags: tags.clone(),
+                    ..Default::default()
+                });
+                rename_file(
+                    file_path,
+                    *no_cache,
+                    *candidates,
+                    chosen,
+                    profile.as_deref(),
+                    dry_run.then(|| save_plan.as_deref()),
+                )
+                .await
+            }
+            Commands::Apply { plan_path } => apply_plan(plan_path).await,
+            Commands::Suggest {
+                file_path,
+                no_cache,
+                candidates,
+            } => suggest_names(file_path, *no_cache, *candidates).await,
+            Commands::ProcessExistingScreenshots {
+                no_cache,
+                batch,
+                images_per_request,
+                dry_run,
+                save_plan,
+            } => {
+                process_existing_screenshots(
+                    *no_cache,
+                    *batch,
+                    *images_per_request,
+                    dry_run.then(|| save_plan.as_deref()),
+                )
+                .await
+            }
+            Commands::BatchStatus { wait } => batch_status(*wait).await,
         }
     }
 }
 
-async fn rename_file(file_name: &str) {
-    let config = Config::fetch().expect("Failed to fetch config");
+async fn rename_file(
+    file_name: &str,
+    no_cache: bool,
+    candidates: Option<u32>,
+    // a name chosen elsewhere, with what should be embedded alongside it
+    chosen: Option<Suggestion>,
+    profile: Option<&str>,
+    // Some on a dry run, with the file to save the plan to
+    dry_run: Option<Option<&str>>,
+) {
+    let mut config = Config::fetch().expect("Failed to fetch config");
     if !config.ready() {
         error!("Config is not ready. Please run `peeksy edit-config` to set it.");
         return;
@@ -91,32 +280,129 @@ async fn rename_file(file_name: &str) {
         return;
     }
 
-    let config = Config::fetch().expect("Failed to fetch config");
-    let ai = OpenAI::new(
-        config.openai_api_key.unwrap(),
-        config.openai_prompt_file_path.unwrap(),
-        config.openai_model.unwrap(),
-    );
+    if no_cache {
+        config.cache = Some(false);
+    }
+    if candidates.is_some() {
+        config.name_candidates = candidates;
+    }
+    if let Some(profile) = profile {
+        config.profile_rules = None;
+        config.default_profile = Some(profile.to_string());
+    }
+    let ai = match provider::from_config(&config) {
+        Ok(ai) => ai,
+        Err(e) => {
+            error!("Failed to setup naming provider: {:?}", e);
+            return;
+        }
+    };
+
+    let mut ss_manager = SSManager::new(ai, RetryPolicy::from_config(&config))
+        .with_collisions(CollisionStrategy::from_config(&config))
+        .with_metadata(config.embed_metadata_enabled());
+    if io::stdin().is_terminal() && io::stdout().is_terminal() {
+        ss_manager = ss_manager.with_picker(Arc::new(TerminalPicker));
+    }
+    if dry_run.is_some() {
+        ss_manager = ss_manager.with_dry_run();
+    }
+
+    // a name chosen elsewhere, e.g. in the app window, skips the provider
+    let resp = match chosen {
+        Some(mut suggestion) => {
+            suggestion.name = ss_manager.clean_name(&file_name, &suggestion.name);
+            ss_manager.rename_random_image(&file_name, suggestion).await
+        }
+        None => ss_manager.process_random_image(&file_name).await,
+    };
+    match resp {
+        Ok(Some(renamed)) if dry_run.is_some() => {
+            finish_plan(&ss_manager, &[renamed], dry_run.flatten())
+        }
+        Ok(Some(renamed)) => {
+            let suggestion = renamed.suggestion;
+            if let Some(description) = suggestion.description {
+                println!("Description: {}", description);
+            }
+            if !suggestion.tags.is_empty() {
+                println!("Tags: {}", suggestion.tags.join(", "));
+            }
+            if let Some(category) = suggestion.category {
+                println!("Category: {}", category);
+            }
+        }
+        Ok(None) => {}
+        Err(e) => error!("Error processing file: {:?}", e),
+    }
+}
+
+async fn suggest_names(file_name: &str, no_cache: bool, candidates: Option<u32>) {
+    let mut config = Config::fetch().expect("Failed to fetch config");
+    if !config.ready() {
+        error!("Config is not ready. Please run `peeksy edit-config` to set it.");
+        return;
+    }
+
+    if no_cache {
+        config.cache = Some(false);
+    }
+    if candidates.is_some() {
+        config.name_candidates = candidates;
+    }
+    let ai = match provider::from_config(&config) {
+        Ok(ai) => ai,
+        Err(e) => {
+            error!("Failed to setup naming provider: {:?}", e);
+            return;
+        }
+    };
 
-    let ss_manager = SSManager::new(ai);
-    let resp = ss_manager.process_random_image(&file_name).await;
-    if let Err(e) = resp {
-        error!("Error processing file: {:?}", e);
+    let ss_manager = SSManager::new(ai, RetryPolicy::from_config(&config));
+    match ss_manager.get_name(&PathBuf::from(file_name)).await {
+        Ok(Some(suggestion)) => println!(
+            "{}",
+            serde_json::to_string(&suggestion).expect("Failed to serialize suggestion")
+        ),
+        Ok(None) => {}
+        Err(e) => error!("Error processing file: {:?}", e),
     }
 }
 
-async fn process_existing_screenshots() {
+async fn process_existing_screenshots(
+    no_cache: bool,
+    batch: bool,
+    images_per_request: Option<u32>,
+    // Some on a dry run, with the file to save the plan to
+    dry_run: Option<Option<&str>>,
+) {
     let ss_dir = get_screenshot_dir();
 
     let files = fs::read_dir(ss_dir).unwrap();
 
-    let config = Config::fetch().unwrap();
-    let ai = OpenAI::new(
-        config.openai_api_key.unwrap(),
-        config.openai_prompt_file_path.unwrap(),
-        config.openai_model.unwrap(),
-    );
-    let ss_manager = SSManager::new(ai);
+    let mut config = Config::fetch().unwrap();
+    if no_cache {
+        config.cache = Some(false);
+    }
+    // bulk runs take the first name for every file
+    config.name_candidates = None;
+    if images_per_request.is_some() {
+        config.images_per_request = images_per_request;
+    }
+    let ai = match provider::from_config(&config) {
+        Ok(ai) => ai,
+        Err(e) => {
+            error!("Failed to setup naming provider: {:?}", e);
+            return;
+        }
+    };
+    if batch && config.get_provider() != "openai" {
+        error!("Batch mode needs the openai provider");
+        return;
+    }
+    let ss_manager = SSManager::new(ai.clone(), RetryPolicy::from_config(&config))
+        .with_collisions(CollisionStrategy::from_config(&config))
+        .with_metadata(config.embed_metadata_enabled());
 
     let mut screenshot = vec![];
     for file in files {
@@ -128,7 +414,27 @@ async fn process_existing_screenshots() {
         }
     }
 
+    if batch {
+        // files waiting in an earlier batch would be paid for twice
+        let pending = BatchJob::pending_files();
+        screenshot.retain(|file| !pending.contains(file));
+    }
+
     println!("Found {} screenshots", screenshot.len());
+    let ledger = Ledger::new(&config);
+    if let Some(mut estimate) = ledger.estimate(ai.model(), screenshot.len()) {
+        if batch {
+            estimate *= BATCH_RATE;
+        }
+        let (today, this_month, _) = ledger.totals();
+        println!(
+            "Estimated cost: up to ${:.4} with {} (${:.4} spent today, ${:.4} this month)",
+            estimate,
+            ai.model(),
+            today.cost_usd,
+            this_month.cost_usd
+        );
+    }
     println!("Do you want to continue? (y/n)");
     let mut input = String::new();
     io::stdin().read_line(&mut input).unwrap();
@@ -138,10 +444,103 @@ async fn process_existing_screenshots() {
         return;
     }
 
-    for file in screenshot {
-        let resp = ss_manager.process_random_image(&file).await;
-        if let Err(e) = resp {
+    if batch {
+        submit_batches(&config, screenshot).await;
+        return;
+    }
+
+    // ctrl-c stops the request in flight and leaves the remaining files alone
+    let cancel = CancelToken::new();
+    // the whole run can be undone with `peeksy undo --run <run>`
+    let run = format!("bulk-{}", time::compact(time::unix_now()));
+    // a dry run journals nothing, so there is nothing to undo
+    if dry_run.is_none() {
+        println!("Run: {}", run);
+    }
+    let mut ss_manager = ss_manager.with_cancel(cancel.clone()).with_run(run);
+    if dry_run.is_some() {
+        ss_manager = ss_manager.with_dry_run();
+    }
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            cancel.cancel();
+        }
+    });
+
+    // images of one profile can share a request when packing is on, a
+    // profile that can't be packed stops the run before anything is renamed
+    let groups = profile::group(&config, screenshot);
+    let mut packers = vec![];
+    for (name, _) in groups.iter() {
+        match profile::resolve(&config, name).and_then(|c| Packer::from_config(&c)) {
+            Ok(packer) => packers.push(packer),
+            Err(e) => {
+                error!(
+                    "Failed to setup packed requests for profile {}: {}",
+                    name, e
+                );
+                return;
+            }
+        }
+    }
+
+    let mut renamed = vec![];
+    'run: for ((_, files), packer) in groups.into_iter().zip(packers) {
+        let size = packer.as_ref().map_or(1, |packer| packer.size());
+
+        for chunk in files.chunks(size) {
+            let resp = match packer.as_ref() {
+                Some(packer) if chunk.len() > 1 => ss_manager.process_packed(packer, chunk).await,
+                _ => ss_manager
+                    .process_random_image(&chunk[0])
+                    .await
+                    .map(|done| done.into_iter().collect()),
+            };
+            let e = match resp {
+                Ok(done) => {
+                    renamed.extend(done);
+                    continue;
+                }
+                Err(e) => e,
+            };
+            if let Some(ProviderError::Cancelled) = e.downcast_ref() {
+                println!("Aborted, remaining files are left untouched");
+                break 'run;
+            }
             error!("Error processing file: {:?}", e);
+
+            // every remaining file would fail the same way
+            if let Some(e) = e.downcast_ref::<ProviderError>() {
+                if e.is_fatal() {
+                    error!("Stopping, remaining files are left untouched");
+                    break 'run;
+                }
+            }
+        }
+    }
+
+    if let Some(save_plan) = dry_run {
+        finish_plan(&ss_manager, &renamed, save_plan);
+    }
+}
+
+// prints the renames of a dry run and saves them for `peeksy apply`
+fn finish_plan(ss_manager: &SSManager, renamed: &[Renamed], save_plan: Option<&str>) {
+    let mut plan = Plan::new();
+    for done in renamed {
+        if let Err(e) = plan.push(done, &ss_manager.origin(&done.from)) {
+            error!("Leaving {:?} out of the plan: {:?}", done.from, e);
+        }
+    }
+    plan.print();
+
+    if let Some(path) = save_plan {
+        match plan.save(Path::new(path)) {
+            Ok(()) => println!(
+                "Saved plan to {}, run `peeksy apply {}` to carry it out",
+                path, path
+            ),
+            Err(e) => error!("{:?}", e),
         }
     }
 }
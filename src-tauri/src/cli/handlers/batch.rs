@@ -0,0 +1,153 @@
+use std::{path::PathBuf, time::Duration};
+
+use log::error;
+
+use crate::{
+    config::config::Config,
+    manager::{
+        batch::{self, BatchJob},
+        image::{CollisionStrategy, SSManager},
+        ledger::{Ledger, SpendingCaps},
+        profile, provider,
+        retry::RetryPolicy,
+    },
+};
+
+// batches usually finish well within the 24h window, no need to poll often
+const POLL_INTERVAL: Duration = Duration::from_secs(60);
+
+// submits one batch per profile, every profile may use another model or prompt
+pub async fn submit_batches(config: &Config, files: Vec<PathBuf>) {
+    if let Err(e) = SpendingCaps::from_config(config).check(&Ledger::new(config)) {
+        error!("Not submitting batch: {}", e);
+        return;
+    }
+
+    for (name, files) in profile::group(config, files) {
+        let openai = match profile::resolve(config, &name).and_then(|c| provider::build_openai(&c))
+        {
+            Ok(openai) => openai,
+            Err(e) => {
+                error!("Failed to setup profile {} for batch mode: {:?}", name, e);
+                continue;
+            }
+        };
+
+        match batch::submit(&openai, &name, &files).await {
+            Ok(job) => println!(
+                "Submitted {} with {} images in {} batches",
+                job.id,
+                job.images(),
+                job.batches.len()
+            ),
+            Err(e) => error!("Failed to submit batch for profile {}: {:?}", name, e),
+        }
+    }
+    println!("Run `peeksy batch-status --wait` to apply the renames once the batches finish");
+}
+
+pub async fn batch_status(wait: bool) {
+    let mut config = Config::fetch().expect("Failed to fetch config");
+    // batch results already hold the first name for every file
+    config.name_candidates = None;
+    let ai = match provider::from_config(&config) {
+        Ok(ai) => ai,
+        Err(e) => {
+            error!("Failed to setup naming provider: {:?}", e);
+            return;
+        }
+    };
+    let ss_manager = SSManager::new(ai, RetryPolicy::from_config(&config))
+        .with_collisions(CollisionStrategy::from_config(&config))
+        .with_metadata(config.embed_metadata_enabled());
+    let ledger = Ledger::new(&config);
+
+    loop {
+        let jobs = BatchJob::pending();
+        if jobs.is_empty() {
+            println!("No pending batches");
+            return;
+        }
+
+        for mut job in jobs {
+            let openai = match profile::resolve(&config, &job.profile)
+                .and_then(|c| provider::build_openai(&c))
+            {
+                Ok(openai) => openai,
+                Err(e) => {
+                    error!(
+                        "Failed to setup profile {} for batch {}: {:?}",
+                        job.profile, job.id, e
+                    );
+                    continue;
+                }
+            };
+
+            // the renames of all batches of a job can be undone together
+            let ss_manager = ss_manager.clone().with_run(format!("batch-{}", job.id));
+            let mut open = vec![];
+            for mut part in std::mem::take(&mut job.batches) {
+                let output_file_id = match batch::refresh(&openai, &mut part).await {
+                    Ok(output_file_id) => output_file_id,
+                    Err(e) => {
+                        error!("Failed to check batch {}: {:?}", part.id, e);
+                        open.push(part);
+                        continue;
+                    }
+                };
+                println!(
+                    "Batch {}: {} ({} images)",
+                    part.id,
+                    part.status,
+                    part.files.len()
+                );
+
+                match output_file_id {
+                    Some(output_file_id) => {
+                        match batch::apply(
+                            &openai,
+                            &job.model,
+                            &part,
+                            &output_file_id,
+                            &ss_manager,
+                            &ledger,
+                        )
+                        .await
+                        {
+                            Ok(renamed) => {
+                                println!("Renamed {} of {} images", renamed.len(), part.files.len())
+                            }
+                            Err(e) => {
+                                error!("Failed to apply batch {}: {:?}", part.id, e);
+                                open.push(part);
+                            }
+                        }
+                    }
+                    // failed, expired or cancelled batches have nothing to apply
+                    None if part.is_final() => println!(
+                        "Batch {} ended as {}, its files are left untouched",
+                        part.id, part.status
+                    ),
+                    None => open.push(part),
+                }
+            }
+
+            job.batches = open;
+            let saved = match job.batches.is_empty() {
+                true => {
+                    job.remove();
+                    Ok(())
+                }
+                false => job.save(),
+            };
+            if let Err(e) = saved {
+                error!("Failed to save batch job {}: {:?}", job.id, e);
+            }
+        }
+
+        if !wait || BatchJob::pending().is_empty() {
+            return;
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
@@ -1,4 +1,6 @@
-use crate::config;
+use std::path::PathBuf;
+
+use crate::{config, manager::profile, utils::ss::get_screenshot_dir};
 
 pub async fn edit_config() {
     let mut config = config::config::Config::fetch().expect("Failed to fetch config");
@@ -6,9 +8,26 @@ pub async fn edit_config() {
     println!("Config edited successfully");
 }
 
-pub async fn current_config() {
+pub async fn current_config(file_path: Option<&str>) {
     let config = config::config::Config::fetch().expect("Failed to fetch config");
     println!("{}", serde_json::to_string_pretty(&config).unwrap());
+
+    // without a file, show what the daemon would use for a new screenshot
+    let path = match file_path {
+        Some(file_path) => PathBuf::from(file_path),
+        None => get_screenshot_dir().join("Screenshot.png"),
+    };
+    let active = profile::select(&config, &path);
+    match profile::resolve(&config, &active) {
+        Ok(resolved) => println!(
+            "Active profile: {} ({}, {}) for {}",
+            active,
+            resolved.get_provider(),
+            resolved.get_naming_style(),
+            path.display()
+        ),
+        Err(e) => println!("Active profile: {} is not usable: {}", active, e),
+    }
 }
 
 pub async fn view_prompt_file() {
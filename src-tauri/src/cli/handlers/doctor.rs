@@ -0,0 +1,117 @@
+use std::time::Instant;
+
+use crate::{
+    config,
+    manager::{
+        gemini::GEMINI_BASE_URL,
+        http::HttpSettings,
+        profile::{self, DEFAULT_PROFILE},
+    },
+};
+
+// endpoints that answer a plain GET, any status means the provider was reached
+fn endpoint(config: &config::config::Config) -> Option<String> {
+    match config.get_provider().as_str() {
+        "openai" => Some(format!(
+            "{}/models",
+            config.get_openai_base_url().trim_end_matches('/')
+        )),
+        "anthropic" => Some("https://api.anthropic.com/v1/models".to_string()),
+        "gemini" => Some(GEMINI_BASE_URL.to_string()),
+        _ => None,
+    }
+}
+
+pub async fn doctor() {
+    let config = config::config::Config::fetch().expect("Failed to fetch config");
+    let settings = HttpSettings::from_config(&config);
+
+    println!("Config ready: {}", config.ready());
+    println!(
+        "Timeouts: connect {:?}, read {:?}",
+        settings.connect_timeout, settings.read_timeout
+    );
+
+    match settings.proxy.as_ref() {
+        Some(proxy) => {
+            println!("Proxy: {}", proxy.display());
+            if let Some(no_proxy) = proxy.no_proxy.as_ref() {
+                println!("No proxy for: {}", no_proxy);
+            }
+        }
+        None => println!("Proxy: none"),
+    }
+
+    match (settings.ca_bundle.as_ref(), settings.certificates()) {
+        (None, _) => println!("Extra CA roots: none"),
+        (Some(path), Ok(certificates)) => println!(
+            "Extra CA roots: {} from {}",
+            certificates.len(),
+            path.display()
+        ),
+        (Some(_), Err(e)) => println!("❌ {}", e),
+    }
+
+    let client = match settings.build() {
+        Ok(client) => client,
+        Err(e) => {
+            println!("❌ Failed to build http client: {}", e);
+            return;
+        }
+    };
+
+    // every profile may point at a different provider or server
+    let mut names = vec![DEFAULT_PROFILE.to_string()];
+    names.extend(config.profiles.iter().flat_map(|p| p.keys().cloned()));
+
+    let mut checked: Vec<String> = vec![];
+    for name in names {
+        let resolved = match profile::resolve(&config, &name) {
+            Ok(resolved) => resolved,
+            Err(e) => {
+                println!("❌ {}", e);
+                continue;
+            }
+        };
+        let url = match endpoint(&resolved) {
+            Some(url) if !checked.contains(&url) => url,
+            Some(_) => continue,
+            None => {
+                println!("{}: {} needs no network", name, resolved.get_provider());
+                continue;
+            }
+        };
+        checked.push(url.clone());
+
+        let host = reqwest::Url::parse(&url)
+            .ok()
+            .and_then(|url| url.host_str().map(|host| host.to_string()))
+            .unwrap_or_default();
+        let route = match settings.proxy.as_ref() {
+            Some(proxy) if !proxy.bypasses(&host) => format!("via {}", proxy.display()),
+            _ => "direct".to_string(),
+        };
+
+        let started = Instant::now();
+        match client.get(&url).send().await {
+            Ok(response) => println!(
+                "✅ {} ({}): {} reached {} in {:?}, status {}",
+                name,
+                resolved.get_provider(),
+                url,
+                route,
+                started.elapsed(),
+                response.status()
+            ),
+            Err(e) => println!(
+                "❌ {} ({}): {} failed {} after {:?}: {:?}",
+                name,
+                resolved.get_provider(),
+                url,
+                route,
+                started.elapsed(),
+                e
+            ),
+        }
+    }
+}
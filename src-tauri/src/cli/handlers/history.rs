@@ -0,0 +1,113 @@
+use std::path::{Path, PathBuf};
+
+use crate::{
+    manager::journal::{self, Journal, JournalEntry},
+    utils::time,
+};
+
+fn file_name(path: &str) -> String {
+    Path::new(path)
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.to_string())
+}
+
+// whether the rename happened in one of `dirs`, on either side of it
+fn in_dir(entry: &JournalEntry, dirs: &[PathBuf]) -> bool {
+    [&entry.from, &entry.to].iter().any(|path| {
+        let parent = Path::new(path).parent();
+        dirs.iter().any(|dir| parent == Some(dir.as_path()))
+    })
+}
+
+// dates are utc days, 2025-01-31
+pub async fn history(since: Option<&str>, until: Option<&str>, dir: Option<&str>, limit: usize) {
+    let entries = Journal::default().entries();
+    let undone = journal::undone(&entries);
+    // entries are stored resolved, older ones only made absolute
+    let dirs = dir.map(|dir| {
+        let dir = Path::new(dir);
+        let absolute = std::path::absolute(dir).unwrap_or_else(|_| dir.to_path_buf());
+        vec![dir.canonicalize().unwrap_or(absolute.clone()), absolute]
+    });
+
+    let matching: Vec<&JournalEntry> = entries
+        .iter()
+        .filter(|entry| since.is_none_or(|since| time::date(entry.timestamp).as_str() >= since))
+        .filter(|entry| until.is_none_or(|until| time::date(entry.timestamp).as_str() <= until))
+        .filter(|entry| dirs.as_ref().is_none_or(|dirs| in_dir(entry, dirs)))
+        .collect();
+
+    if matching.is_empty() {
+        println!("No renames found");
+        return;
+    }
+
+    // newest last, like a log
+    let skip = matching.len().saturating_sub(limit);
+    for entry in matching.into_iter().skip(skip) {
+        let note = match (entry.undo_of.as_ref(), undone.contains(&entry.id)) {
+            (Some(id), _) => format!("undo of {}", id),
+            (None, true) => "undone".to_string(),
+            (None, false) => String::new(),
+        };
+        println!(
+            "{}  {}  {} -> {}  [{} {} {}{}]  {}",
+            entry.id,
+            time::datetime(entry.timestamp),
+            file_name(&entry.from),
+            file_name(&entry.to),
+            entry.profile,
+            entry.provider,
+            entry.model,
+            entry
+                .run
+                .as_ref()
+                .map(|run| format!(" {}", run))
+                .unwrap_or_default(),
+            note
+        );
+    }
+}
+
+// Reverts renames, newest first so chains of renames unwind in order. Only
+// renames that are not undos themselves and not yet undone are picked.
+pub async fn undo(last: Option<usize>, id: Option<&str>, run: Option<&str>) {
+    let journal = Journal::default();
+    let entries = journal.entries();
+    let undone = journal::undone(&entries);
+
+    let candidates = entries
+        .iter()
+        .rev()
+        .filter(|entry| entry.undo_of.is_none() && !undone.contains(&entry.id));
+    let targets: Vec<&JournalEntry> = match (id, run) {
+        (Some(id), _) => candidates.filter(|entry| entry.id == id).collect(),
+        (None, Some(run)) => candidates
+            .filter(|entry| entry.run.as_deref() == Some(run))
+            .collect(),
+        (None, None) => candidates.take(last.unwrap_or(1)).collect(),
+    };
+
+    if targets.is_empty() {
+        println!("Nothing to undo");
+        return;
+    }
+
+    let mut reverted = 0;
+    for entry in targets.iter() {
+        match journal.undo(entry) {
+            Ok(_) => {
+                println!(
+                    "Undid {}: {} -> {}",
+                    entry.id,
+                    file_name(&entry.to),
+                    file_name(&entry.from)
+                );
+                reverted += 1;
+            }
+            Err(e) => println!("Refusing to undo {}: {}", entry.id, e),
+        }
+    }
+    println!("Undid {} of {} renames", reverted, targets.len());
+}
@@ -1,3 +1,8 @@
+pub mod batch;
 pub mod config;
+pub mod doctor;
+pub mod history;
 pub mod log;
+pub mod plan;
 pub mod status;
+pub mod usage;
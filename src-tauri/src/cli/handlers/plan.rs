@@ -0,0 +1,41 @@
+use std::path::Path;
+
+use crate::{
+    config::config::Config,
+    manager::{
+        journal::Journal,
+        plan::{self, Plan},
+    },
+    utils::time,
+};
+
+// carries out a reviewed plan, the renames can be undone together with
+// `peeksy undo --run <run>`
+pub async fn apply_plan(plan_path: &str) {
+    let plan = match Plan::load(Path::new(plan_path)) {
+        Ok(plan) => plan,
+        Err(e) => {
+            println!("{}", e);
+            return;
+        }
+    };
+
+    let embed_metadata = Config::fetch()
+        .expect("Failed to fetch config")
+        .embed_metadata_enabled();
+    let journal = Journal::default();
+    let run = format!("plan-{}", time::compact(time::unix_now()));
+    println!("Run: {}", run);
+
+    let mut applied = 0;
+    for entry in plan.entries.iter() {
+        match plan::apply(entry, &journal, &run, embed_metadata) {
+            Ok(()) => {
+                println!("{} -> {}", entry.from, entry.to);
+                applied += 1;
+            }
+            Err(e) => println!("Skipping {}: {}", entry.from, e),
+        }
+    }
+    println!("Applied {} of {} renames", applied, plan.entries.len());
+}
@@ -0,0 +1,37 @@
+use crate::{
+    config,
+    manager::ledger::{get_seat, Ledger, Totals},
+};
+
+fn print_totals(label: &str, totals: &Totals) {
+    println!(
+        "{:<12} {:>8} requests {:>12} in {:>10} out  ${:.4}",
+        label, totals.requests, totals.input_tokens, totals.output_tokens, totals.cost_usd
+    );
+}
+
+pub async fn usage() {
+    let config = config::config::Config::fetch().expect("Failed to fetch config");
+    let ledger = Ledger::new(&config);
+    let (today, this_month, all_time) = ledger.totals();
+
+    println!("Seat: {}", get_seat());
+    print_totals("Today", &today);
+    print_totals("This month", &this_month);
+    print_totals("All time", &all_time);
+
+    if let Some(cap) = config.daily_spend_cap_usd {
+        println!(
+            "Daily cap: ${:.2} (${:.2} left)",
+            cap,
+            (cap - today.cost_usd).max(0.0)
+        );
+    }
+    if let Some(cap) = config.monthly_spend_cap_usd {
+        println!(
+            "Monthly cap: ${:.2} (${:.2} left)",
+            cap,
+            (cap - this_month.cost_usd).max(0.0)
+        );
+    }
+}
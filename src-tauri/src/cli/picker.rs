@@ -0,0 +1,34 @@
+use std::path::PathBuf;
+
+use dialoguer::{theme::ColorfulTheme, Select};
+use log::error;
+
+use crate::manager::image::NamePicker;
+
+const KEEP_ORIGINAL: &str = "(keep original name)";
+
+// Arrow key menu for `peeksy rename`, only used when attached to a terminal
+#[derive(Debug, Clone, Default)]
+pub struct TerminalPicker;
+
+impl NamePicker for TerminalPicker {
+    fn pick(&self, path: &PathBuf, candidates: &[String]) -> Option<String> {
+        let mut items: Vec<&str> = candidates.iter().map(|name| name.as_str()).collect();
+        items.push(KEEP_ORIGINAL);
+
+        let selection = Select::with_theme(&ColorfulTheme::default())
+            .with_prompt(format!("Pick a name for {}", path.display()))
+            .items(&items)
+            .default(0)
+            .interact_opt();
+
+        match selection {
+            Ok(Some(index)) if index < candidates.len() => Some(candidates[index].clone()),
+            Ok(_) => None,
+            Err(e) => {
+                error!("Failed to read selection, using first name: {:?}", e);
+                candidates.first().cloned()
+            }
+        }
+    }
+}
@@ -7,6 +7,8 @@ pub struct Config {
     pub openai_api_key: Option<String>,
     pub openai_prompt_file_path: Option<String>,
     pub openai_model: Option<String>,
+    // shown in the config view, the cli falls back to openai when unset
+    pub provider: Option<String>,
 }
 
 impl Config {
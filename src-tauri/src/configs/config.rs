@@ -1,15 +1,59 @@
 use log::{error, info};
 use serde::{Deserialize, Serialize};
 use serde_json;
-use std::{fs::File, io};
+use std::{collections::HashMap, fs::File, io, path::PathBuf};
 
-use crate::config::setup;
+use crate::{
+    config::setup,
+    manager::{
+        ai::DEFAULT_BASE_URL,
+        http::{DEFAULT_CONNECT_TIMEOUT_SECS, DEFAULT_READ_TIMEOUT_SECS},
+        ledger::ModelPrice,
+        profile::{Profile, ProfileRule},
+    },
+};
+
+const MAX_NAME_CANDIDATES: u32 = 5;
+const MAX_IMAGES_PER_REQUEST: u32 = 10;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Config {
     pub openai_api_key: Option<String>,
     pub openai_prompt_file_path: Option<String>,
     pub openai_model: Option<String>,
+    pub provider: Option<String>,
+    pub openai_base_url: Option<String>,
+    pub anthropic_api_key: Option<String>,
+    pub anthropic_model: Option<String>,
+    pub gemini_api_key: Option<String>,
+    pub gemini_model: Option<String>,
+    pub offline_fallback: Option<bool>,
+    pub max_attempts: Option<u32>,
+    pub structured_output: Option<bool>,
+    pub max_image_dimension: Option<u32>,
+    pub image_detail: Option<String>,
+    pub cache: Option<bool>,
+    pub model_prices: Option<HashMap<String, ModelPrice>>,
+    pub daily_spend_cap_usd: Option<f64>,
+    pub monthly_spend_cap_usd: Option<f64>,
+    pub name_candidates: Option<u32>,
+    pub naming_style: Option<String>,
+    pub profiles: Option<HashMap<String, Profile>>,
+    pub profile_rules: Option<Vec<ProfileRule>>,
+    pub default_profile: Option<String>,
+    pub connect_timeout_secs: Option<u64>,
+    pub read_timeout_secs: Option<u64>,
+    pub https_proxy: Option<String>,
+    pub no_proxy: Option<String>,
+    pub proxy_username: Option<String>,
+    pub proxy_password: Option<String>,
+    pub ca_bundle_path: Option<String>,
+    pub fake_fixtures_path: Option<String>,
+    pub fixture_mode: Option<String>,
+    pub fixture_dir: Option<String>,
+    pub images_per_request: Option<u32>,
+    pub collision_strategy: Option<String>,
+    pub embed_metadata: Option<bool>,
 }
 
 impl Config {
@@ -28,19 +72,55 @@ impl Config {
     }
 
     pub fn ready(&self) -> bool {
-        if !self.openai_api_key_exists() {
+        // the local ocr namer needs no credentials, anything missing below
+        // just means every image gets named offline
+        if self.get_provider() == "ocr"
+            || self.get_provider() == "fake"
+            || self.offline_fallback_enabled()
+        {
+            return true;
+        }
+
+        if self.get_provider() == "openai"
+            && !self.openai_base_url_exists()
+            && !self.openai_api_key_exists()
+        {
             let err = "[Peeksy Ready] OpenAI API key is not set";
             error!("{}", err);
             return false;
         }
 
+        if self.get_provider() == "anthropic" && !self.anthropic_api_key_exists() {
+            let err = "[Peeksy Ready] Anthropic API key is not set";
+            error!("{}", err);
+            return false;
+        }
+
+        if self.get_provider() == "anthropic" && !self.anthropic_model_exists() {
+            let err = "[Peeksy Ready] Anthropic model is not set";
+            error!("{}", err);
+            return false;
+        }
+
         if !self.openai_prompt_file_path_exists() {
             let err = "[Peeksy Ready] OpenAI prompt file path is not set";
             error!("{}", err);
             return false;
         }
 
-        if !self.openai_model_exists() {
+        if self.get_provider() == "gemini" && !self.gemini_api_key_exists() {
+            let err = "[Peeksy Ready] Gemini API key is not set";
+            error!("{}", err);
+            return false;
+        }
+
+        if self.get_provider() == "gemini" && !self.gemini_model_exists() {
+            let err = "[Peeksy Ready] Gemini model is not set";
+            error!("{}", err);
+            return false;
+        }
+
+        if self.get_provider() == "openai" && !self.openai_model_exists() {
             let err = "[Peeksy Ready] OpenAI model is not set";
             error!("{}", err);
             return false;
@@ -65,6 +145,46 @@ impl Config {
         }
     }
 
+    pub fn openai_base_url_exists(&self) -> bool {
+        if let Some(url) = self.openai_base_url.as_ref() {
+            !url.is_empty()
+        } else {
+            false
+        }
+    }
+
+    pub fn anthropic_api_key_exists(&self) -> bool {
+        if let Some(key) = self.anthropic_api_key.as_ref() {
+            !key.is_empty()
+        } else {
+            false
+        }
+    }
+
+    pub fn anthropic_model_exists(&self) -> bool {
+        if let Some(model) = self.anthropic_model.as_ref() {
+            !model.is_empty()
+        } else {
+            false
+        }
+    }
+
+    pub fn gemini_api_key_exists(&self) -> bool {
+        if let Some(key) = self.gemini_api_key.as_ref() {
+            !key.is_empty()
+        } else {
+            false
+        }
+    }
+
+    pub fn gemini_model_exists(&self) -> bool {
+        if let Some(model) = self.gemini_model.as_ref() {
+            !model.is_empty()
+        } else {
+            false
+        }
+    }
+
     pub fn openai_prompt_file_path_exists(&self) -> bool {
         if let Some(path) = self.openai_prompt_file_path.as_ref() {
             !path.is_empty()
@@ -77,7 +197,6 @@ impl Config {
         self.openai_api_key.clone()
     }
 
-
     pub fn get_openai_prompt_file_path(&self) -> Option<String> {
         self.openai_prompt_file_path.clone()
     }
@@ -86,6 +205,144 @@ impl Config {
         self.openai_model.clone()
     }
 
+    pub fn get_anthropic_api_key(&self) -> Option<String> {
+        self.anthropic_api_key.clone()
+    }
+
+    pub fn get_anthropic_model(&self) -> Option<String> {
+        self.anthropic_model.clone()
+    }
+
+    pub fn get_gemini_api_key(&self) -> Option<String> {
+        self.gemini_api_key.clone()
+    }
+
+    pub fn get_gemini_model(&self) -> Option<String> {
+        self.gemini_model.clone()
+    }
+
+    // points the openai provider at any compatible server, e.g. ollama at
+    // http://localhost:11434/v1
+    pub fn get_openai_base_url(&self) -> String {
+        if self.openai_base_url_exists() {
+            self.openai_base_url.clone().unwrap()
+        } else {
+            DEFAULT_BASE_URL.to_string()
+        }
+    }
+
+    // attempts per image for rate limited or failing provider calls
+    pub fn get_max_attempts(&self) -> u32 {
+        self.max_attempts.unwrap_or(4)
+    }
+
+    // seconds to wait for a connection to the provider
+    pub fn get_connect_timeout_secs(&self) -> u64 {
+        self.connect_timeout_secs
+            .unwrap_or(DEFAULT_CONNECT_TIMEOUT_SECS)
+    }
+
+    // seconds to wait for the next bytes of a response before giving up
+    pub fn get_read_timeout_secs(&self) -> u64 {
+        self.read_timeout_secs.unwrap_or(DEFAULT_READ_TIMEOUT_SECS)
+    }
+
+    // where record mode stores provider answers and replay mode reads them
+    pub fn get_fixture_dir(&self) -> PathBuf {
+        match self.fixture_dir.as_ref() {
+            Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => dirs::config_dir().unwrap().join("peeksy").join("fixtures"),
+        }
+    }
+
+    // ask the provider for description, tags and category next to the name
+    pub fn structured_output_enabled(&self) -> bool {
+        self.structured_output.unwrap_or(false)
+    }
+
+    // names to choose from on an interactive rename, the daemon and bulk runs
+    // always take the first one
+    pub fn get_name_candidates(&self) -> u32 {
+        self.name_candidates
+            .unwrap_or(1)
+            .clamp(1, MAX_NAME_CANDIDATES)
+    }
+
+    // images packed into one request on bulk runs, 1 sends every image on
+    // its own
+    pub fn get_images_per_request(&self) -> u32 {
+        self.images_per_request
+            .unwrap_or(1)
+            .clamp(1, MAX_IMAGES_PER_REQUEST)
+    }
+
+    // Packed requests go straight to openai, past the fixture recorder and
+    // the ocr fallback, so packing is refused wherever those are in play.
+    // `Packer` keeps its own cache entries.
+    pub fn check_packing(&self) -> Result<(), anyhow::Error> {
+        if self.get_images_per_request() < 2 {
+            return Ok(());
+        }
+        if self.get_provider() != "openai" {
+            return Err(anyhow::anyhow!(
+                "Packing images into one request needs the openai provider, not {}",
+                self.get_provider()
+            ));
+        }
+        if let Some(mode) = self.fixture_mode.as_deref().filter(|mode| !mode.is_empty()) {
+            return Err(anyhow::anyhow!(
+                "Packing images into one request does not work in fixture mode {}",
+                mode
+            ));
+        }
+        if self.offline_fallback_enabled() {
+            return Err(anyhow::anyhow!(
+                "Packing images into one request does not work with the offline fallback"
+            ));
+        }
+        Ok(())
+    }
+
+    // suffix, timestamp, ask or skip when the suggested name is taken
+    pub fn get_collision_strategy(&self) -> String {
+        match self.collision_strategy.as_ref() {
+            Some(strategy) if !strategy.is_empty() => strategy.to_lowercase(),
+            _ => "suffix".to_string(),
+        }
+    }
+
+    // kebab or snake, kebab unless a profile says otherwise
+    pub fn get_naming_style(&self) -> String {
+        match self.naming_style.as_ref() {
+            Some(style) if !style.is_empty() => style.to_lowercase(),
+            _ => "kebab".to_string(),
+        }
+    }
+
+    // reuse earlier suggestions for identical images, on by default
+    pub fn cache_enabled(&self) -> bool {
+        self.cache.unwrap_or(true)
+    }
+
+    // write description and tags into png, jpeg and webp files on rename,
+    // needs structured output to have anything to write
+    pub fn embed_metadata_enabled(&self) -> bool {
+        self.embed_metadata.unwrap_or(false)
+    }
+
+    // name images locally with ocr when the provider is unusable
+    pub fn offline_fallback_enabled(&self) -> bool {
+        self.offline_fallback.unwrap_or(false)
+    }
+
+    // naming provider used by the daemon and cli, defaults to openai
+    pub fn get_provider(&self) -> String {
+        match self.provider.as_ref() {
+            Some(provider) if !provider.is_empty() => provider.to_lowercase(),
+            _ => "openai".to_string(),
+        }
+    }
+
     // sets the api key and prompt file path
     pub fn edit_config(&mut self) -> Result<(), Box<dyn std::error::Error>> {
         let mut updated = false;
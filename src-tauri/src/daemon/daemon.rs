@@ -1,8 +1,4 @@
-use std::sync::{
-    atomic::{AtomicBool, Ordering},
-    mpsc::{channel, RecvTimeoutError},
-    Arc,
-};
+use std::sync::mpsc::{channel, RecvTimeoutError};
 
 use log::{error, info};
 use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
@@ -10,13 +6,22 @@ use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
 use crate::{
     config,
     daemon::pid,
-    manager::{ai::OpenAI, image::SSManager},
+    manager::{
+        cancel::CancelToken,
+        error::ProviderError,
+        image::{CollisionStrategy, SSManager},
+        provider,
+        retry::RetryPolicy,
+    },
     utils::ss::get_screenshot_dir,
 };
 
-use tokio::signal;
+use tokio::signal::{
+    self,
+    unix::{signal as unix_signal, SignalKind},
+};
 
-async fn daemon(shutdown: Arc<AtomicBool>) {
+async fn daemon(shutdown: CancelToken) {
     let screenshot_dir = get_screenshot_dir();
     info!("Peeksy is running on {}", screenshot_dir.display());
 
@@ -28,16 +33,27 @@ async fn daemon(shutdown: Arc<AtomicBool>) {
         .watch(&screenshot_dir, RecursiveMode::NonRecursive)
         .expect("Failed to watch directory");
 
-    let config = config::config::Config::fetch().expect("Failed to fetch config");
-    let ai = OpenAI::new(
-        config.openai_api_key.unwrap(),
-        config.openai_prompt_file_path.unwrap(),
-        config.openai_model.unwrap(),
-    );
-    let ss_controller = SSManager::new(ai);
+    let mut config = config::config::Config::fetch().expect("Failed to fetch config");
+    // nobody is there to pick, don't pay for alternatives
+    config.name_candidates = None;
+    let ai = match provider::from_config(&config) {
+        Ok(ai) => ai,
+        Err(e) => {
+            error!("Failed to setup naming provider: {}. Use `peeksy edit-config` to update Peeksy config", e);
+            shutdown.cancel();
+            return;
+        }
+    };
+    if let Err(e) = ai.probe().await {
+        error!("Naming provider {} is not ready: {:?}", ai.id(), e);
+    }
+    let ss_controller = SSManager::new(ai, RetryPolicy::from_config(&config))
+        .with_cancel(shutdown.clone())
+        .with_collisions(CollisionStrategy::from_config(&config))
+        .with_metadata(config.embed_metadata_enabled());
 
     info!("Setup complete, Peeksy is ready!");
-    while !shutdown.load(Ordering::Relaxed) {
+    while !shutdown.is_cancelled() {
         match rx.recv_timeout(std::time::Duration::from_millis(100)) {
             Ok(event) => {
                 if let Ok(Event {
@@ -47,10 +63,22 @@ async fn daemon(shutdown: Arc<AtomicBool>) {
                 }) = event
                 {
                     for path in paths {
+                        if shutdown.is_cancelled() {
+                            break;
+                        }
                         info!("Detected new file: {:?}", path);
                         let resp = ss_controller.process_new_ss(&path).await;
                         if let Err(e) = resp {
+                            if let Some(ProviderError::Cancelled) = e.downcast_ref() {
+                                info!("Shutting down, left {:?} untouched", path);
+                                continue;
+                            }
                             error!("Error processing file: {:?}", e);
+                            if let Some(e) = e.downcast_ref::<ProviderError>() {
+                                if e.is_fatal() {
+                                    error!("Naming provider needs attention, screenshots are left untouched until it is fixed. Use `peeksy edit-config` to update Peeksy config");
+                                }
+                            }
                         }
                     }
                 }
@@ -77,8 +105,10 @@ pub async fn run() {
     // save the pid
     pid::save_pid(new_pid);
 
-    let shutdown = Arc::new(AtomicBool::new(false));
+    let shutdown = CancelToken::new();
     let shutdown_clone = shutdown.clone();
+    // launchctl unload stops the daemon with SIGTERM
+    let mut terminate = unix_signal(SignalKind::terminate()).expect("Failed to listen for SIGTERM");
 
     let peeksy_thread_handler = tokio::spawn(async move {
         info!("Starting Peeksy thread...");
@@ -89,10 +119,17 @@ pub async fn run() {
     tokio::select! {
         _ = signal::ctrl_c() => {
             info!("Received shutdown signal. Shutting down...");
-            shutdown.store(true, Ordering::Relaxed);
+            shutdown.cancel();
+        }
+        _ = terminate.recv() => {
+            info!("Received terminate signal. Shutting down...");
+            shutdown.cancel();
         }
         _ = peeksy_thread_handler => {
-            error!("Peeksy thread exited unexpectedly");
+            // the thread cancels itself when it can't start
+            if !shutdown.is_cancelled() {
+                error!("Peeksy thread exited unexpectedly");
+            }
         }
     }
 
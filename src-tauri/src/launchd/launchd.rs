@@ -82,10 +82,7 @@ impl LaunchD {
     }
 
     pub async fn is_loaded(&self) -> bool {
-        match self.status().await {
-            Ok(_) => true,
-            Err(_) => false,
-        }
+        self.status().await.is_ok()
     }
 
     pub async fn is_running(&self) -> bool {
@@ -1,8 +1,10 @@
 pub mod config;
 pub mod files;
+pub mod rename;
 pub mod tray;
 
 use config::Config;
+use rename::{PendingRename, PendingRenames};
 
 #[tauri::command]
 fn get_config() -> Config {
@@ -20,6 +22,47 @@ fn get_finder_selection_single() -> Option<String> {
     files::macos::get_finder_selection_single()
 }
 
+#[tauri::command]
+fn get_pending_renames(state: tauri::State<PendingRenames>) -> Vec<PendingRename> {
+    state.0.lock().unwrap().clone()
+}
+
+#[tauri::command]
+fn apply_rename(state: tauri::State<PendingRenames>, file: String, name: String) -> bool {
+    let pending = state
+        .0
+        .lock()
+        .unwrap()
+        .iter()
+        .find(|pending| pending.file == file)
+        .cloned()
+        .unwrap_or_else(|| PendingRename {
+            file: file.clone(),
+            candidates: vec![],
+            description: None,
+            tags: vec![],
+        });
+    let renamed = rename::apply(&pending, &name);
+    // a failed pick stays pending so it can be retried or kept
+    if renamed {
+        state
+            .0
+            .lock()
+            .unwrap()
+            .retain(|pending| pending.file != file);
+    }
+    renamed
+}
+
+#[tauri::command]
+fn skip_rename(state: tauri::State<PendingRenames>, file: String) {
+    state
+        .0
+        .lock()
+        .unwrap()
+        .retain(|pending| pending.file != file);
+}
+
 #[tauri::command]
 fn close_window(window: tauri::Window) {
     window.close().unwrap();
@@ -28,13 +71,17 @@ fn close_window(window: tauri::Window) {
 pub fn run() {
     let app = tauri::Builder::default()
         .plugin(tauri_plugin_positioner::init())
+        .manage(PendingRenames::default())
         .setup(|app| tray::tray::setup(app))
         .plugin(tauri_plugin_opener::init())
         .invoke_handler(tauri::generate_handler![
             close_window,
             get_config,
             get_finder_selection,
-            get_finder_selection_single
+            get_finder_selection_single,
+            get_pending_renames,
+            apply_rename,
+            skip_rename
         ])
         .build(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -16,7 +16,6 @@ fn get_log_path() -> PathBuf {
 fn get_info_writable(log_path: PathBuf) -> File {
     let info_path = log_path.join("info.log");
     let info_writable = OpenOptions::new()
-        .write(true)
         .create(true)
         .append(true)
         .open(&info_path)
@@ -27,7 +26,6 @@ fn get_info_writable(log_path: PathBuf) -> File {
 fn get_error_writable(log_path: PathBuf) -> File {
     let error_path = log_path.join("error.log");
     let error_writable = OpenOptions::new()
-        .write(true)
         .create(true)
         .append(true)
         .open(&error_path)
@@ -38,7 +36,6 @@ fn get_error_writable(log_path: PathBuf) -> File {
 fn get_debug_writable(log_path: PathBuf) -> File {
     let debug_path = log_path.join("debug.log");
     let debug_writable = OpenOptions::new()
-        .write(true)
         .create(true)
         .append(true)
         .open(&debug_path)
@@ -1,55 +1,207 @@
-#![allow(deprecated)]
-use log::info;
+use async_trait::async_trait;
+use log::{info, warn};
 use reqwest::header::{AUTHORIZATION, CONTENT_TYPE};
 use serde_json::json;
-use std::{fs::File, io::Read, path::PathBuf};
+use std::path::PathBuf;
+
+use crate::manager::{
+    encode::{EncodedImage, ImageOptions},
+    error::ProviderError,
+    http, prompt,
+    provider::{suggestion_schema, system_prompt, NamingProvider, Suggestion, Usage},
+};
+
+pub const DEFAULT_BASE_URL: &str = "https://api.openai.com/v1";
+
+// longest side of each image when several share one request
+const PACKED_MAX_DIMENSION: u32 = 512;
+
+const PACKED_SYSTEM_PROMPT: &str = r#"You are a filename generation bot. You get several images, each introduced by its index.
+                         Answer with a single JSON array and nothing else, one object per image: {"index": <index>, "filename": "<name>"}.
+                         Each filename is lowercase, hyphenated and 3 to 8 words without extension."#;
+
+const PACKED_STRUCTURED_SYSTEM_PROMPT: &str = r#"You are a filename generation bot. You get several images, each introduced by its index.
+                         Answer with a single JSON array and nothing else, one object per image with the fields: "index" the image index,
+                         "filename" a lowercase hyphenated filename of 3 to 8 words without extension, "description" one sentence describing the image,
+                         "tags" up to 8 lowercase keywords, "category" one word such as screenshot, photo, document, diagram, artwork or meme,
+                         "confidence" a number between 0 and 1 for how sure you are about the filename."#;
 
 #[derive(Debug, Clone)]
 pub struct OpenAI {
-    api_key: String,
+    api_key: Option<String>,
     prompt: String,
     model: String,
+    base_url: String,
+    structured: bool,
+    image: ImageOptions,
+    // names asked for per image, sent as `n`
+    candidates: u32,
+    client: reqwest::Client,
+}
+
+#[derive(Debug, Clone)]
+pub struct ModelCapabilities {
+    pub available: bool,
+    // None when the server does not tell us (plain OpenAI compatible servers)
+    pub vision: Option<bool>,
 }
 
 impl OpenAI {
-    pub fn new(api_key: String, prompt: String, model: String) -> Self {
+    pub fn new(
+        api_key: Option<String>,
+        prompt: String,
+        model: String,
+        base_url: String,
+        structured: bool,
+        image: ImageOptions,
+        candidates: u32,
+    ) -> Self {
         Self {
             api_key,
             prompt,
             model,
+            base_url: base_url.trim_end_matches('/').to_string(),
+            structured,
+            image,
+            candidates,
+            client: http::client(),
         }
     }
 
-    pub async fn get_name(&self, image_path: &PathBuf) -> String {
-        info!("Getting name for image: {:?}", image_path.display());
-        // Read the image file and base64-encode it
-        let mut file = File::open(image_path).expect("Failed to open image file");
-        let mut buffer = Vec::new();
-        file.read_to_end(&mut buffer)
-            .expect("Failed to read image file");
-        let encoded_image = base64::encode(&buffer);
+    fn endpoint(&self, path: &str) -> String {
+        format!("{}/{}", self.base_url, path)
+    }
+
+    // local servers (ollama, llama.cpp, lm studio) usually run without auth,
+    // so the header is only sent when a key is configured
+    fn authorize(&self, request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        match self.api_key.as_ref() {
+            Some(key) => request.header(AUTHORIZATION, format!("Bearer {}", key)),
+            None => request,
+        }
+    }
+
+    // checks that the configured model is served by the endpoint and, where the
+    // server exposes it, that the model accepts image input
+    pub async fn probe_model(&self) -> Result<ModelCapabilities, anyhow::Error> {
+        let response = self
+            .authorize(self.client.get(self.endpoint("models")))
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(anyhow::anyhow!(
+                "Failed to list models at {}, status: {}",
+                self.base_url,
+                response.status()
+            ));
+        }
+
+        let models: serde_json::Value = serde_json::from_str(&response.text().await?)?;
+        let available = models["data"]
+            .as_array()
+            .map(|data| {
+                data.iter()
+                    .any(|model| model["id"].as_str() == Some(self.model.as_str()))
+            })
+            .unwrap_or(false);
+
+        Ok(ModelCapabilities {
+            available,
+            vision: self.probe_ollama_vision().await,
+        })
+    }
+
+    // ollama reports model capabilities on its native api, next to /v1
+    async fn probe_ollama_vision(&self) -> Option<bool> {
+        let root = self.base_url.strip_suffix("/v1")?;
+        let response = self
+            .client
+            .post(format!("{}/api/show", root))
+            .header(CONTENT_TYPE, "application/json")
+            .body(json!({ "model": self.model }).to_string())
+            .send()
+            .await
+            .ok()?;
+
+        if !response.status().is_success() {
+            return None;
+        }
+
+        let show: serde_json::Value = serde_json::from_str(&response.text().await.ok()?).ok()?;
+        let capabilities = show["capabilities"].as_array()?;
+        Some(
+            capabilities
+                .iter()
+                .any(|capability| capability.as_str() == Some("vision")),
+        )
+    }
+
+    // an authorized request to `path` below the base url
+    pub fn request(&self, method: reqwest::Method, path: &str) -> reqwest::RequestBuilder {
+        self.authorize(self.client.request(method, self.endpoint(path)))
+    }
+
+    // sends the request and returns the body, error statuses are mapped to
+    // provider errors
+    pub async fn send(&self, request: reqwest::RequestBuilder) -> Result<String, ProviderError> {
+        let response = request.send().await?;
+
+        let status = response.status();
+        let headers = response.headers().clone();
+        let response_text = response.text().await?;
+
+        if !status.is_success() {
+            // error bodies look like {"error": {"message": ..., "code": ...}}
+            let message = serde_json::from_str::<serde_json::Value>(&response_text)
+                .ok()
+                .and_then(|body| {
+                    let error = &body["error"];
+                    let message = error["message"].as_str()?;
+                    Some(match error["code"].as_str() {
+                        Some(code) => format!("{} ({})", message, code),
+                        None => message.to_string(),
+                    })
+                })
+                .unwrap_or(response_text);
+            return Err(ProviderError::from_response(status, &headers, message));
+        }
+        Ok(response_text)
+    }
+
+    // the chat completion request for one image, also used for batch lines
+    pub fn payload(&self, image_path: &PathBuf) -> Result<serde_json::Value, ProviderError> {
+        self.payload_with_prompt(image_path, &prompt::render(&self.prompt, image_path))
+    }
+
+    fn payload_with_prompt(
+        &self,
+        image_path: &PathBuf,
+        text: &str,
+    ) -> Result<serde_json::Value, ProviderError> {
+        // Read the image file, re-encode it if needed and base64-encode it
+        let image = EncodedImage::load(image_path, &self.image)?;
 
         // Create the JSON payload
-        let payload = json!({
+        let mut payload = json!({
             "model": self.model,
             "messages": [
                     {
                         "role": "system",
-                        "content": r#"You are a filename generation bot. You must return only a filename based on the attached image. No explanations.
-                         No descriptions. No punctuation. No quotes. No code blocks. Just a lowercase hyphenated filename of 3 to 8 words in plain text."#
+                        "content": system_prompt(self.structured)
                     },
                     {
                         "role": "user",
                         "content": [
                     {
                         "type": "text",
-                        "text": self.prompt
+                        "text": text
                     },
                     {
                         "type": "image_url",
                         "image_url": {
-                            "url": format!("data:image/png;base64,{}", encoded_image),
-                            "detail": "low"
+                            "url": image.data_url(),
+                            "detail": self.image.detail
                         }
                     }
                 ]
@@ -57,31 +209,301 @@ impl OpenAI {
             ],
         });
 
-        // Send the request to OpenAI API
-        self.make_ai_request(&payload).await
+        if self.structured {
+            payload["response_format"] = json!({
+                "type": "json_schema",
+                "json_schema": {
+                    "name": "filename_suggestion",
+                    "strict": true,
+                    "schema": suggestion_schema()
+                }
+            });
+        }
+
+        if self.candidates > 1 {
+            payload["n"] = json!(self.candidates);
+        }
+
+        Ok(payload)
     }
 
-    async fn make_ai_request(&self, payload: &serde_json::Value) -> String {
-        let response = reqwest::Client::new()
-            .post("https://api.openai.com/v1/chat/completions")
-            .header(AUTHORIZATION, format!("Bearer {}", self.api_key))
-            .header(CONTENT_TYPE, "application/json")
-            .body(payload.to_string())
-            .send()
-            .await
-            .expect("Failed to send request");
+    async fn make_ai_request(
+        &self,
+        payload: &serde_json::Value,
+    ) -> Result<Suggestion, ProviderError> {
+        let response_text = self
+            .send(
+                self.request(reqwest::Method::POST, "chat/completions")
+                    .header(CONTENT_TYPE, "application/json")
+                    .body(payload.to_string()),
+            )
+            .await?;
+
+        let response_json: serde_json::Value = serde_json::from_str(&response_text)?;
+        self.parse_completion(&response_json)
+    }
 
-        // Parse and extract the filename
-        let response_text = response.text().await.expect("Failed to get response text");
-        let response_json: serde_json::Value =
-            serde_json::from_str(&response_text).expect("Failed to parse response");
+    // extracts the suggestion from a chat completion, shared with batch results
+    pub fn parse_completion(
+        &self,
+        response_json: &serde_json::Value,
+    ) -> Result<Suggestion, ProviderError> {
+        let choice = &response_json["choices"][0];
 
-        let name = response_json["choices"][0]["message"]["content"]
+        if let Some(refusal) = choice["message"]["refusal"].as_str() {
+            return Err(ProviderError::ContentRefused(refusal.to_string()));
+        }
+        if choice["finish_reason"] == "content_filter" {
+            return Err(ProviderError::ContentRefused(
+                "response was filtered".to_string(),
+            ));
+        }
+
+        // one choice per requested candidate, filtered or empty ones are dropped
+        let choices = response_json["choices"]
+            .as_array()
+            .map(|choices| {
+                choices
+                    .iter()
+                    .filter(|choice| choice["finish_reason"] != "content_filter")
+                    .filter_map(|choice| choice["message"]["content"].as_str())
+                    .filter_map(|content| Suggestion::parse(content, self.structured).ok())
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let mut suggestion = match Suggestion::merge(choices) {
+            Some(suggestion) => suggestion,
+            None => {
+                return Err(ProviderError::MalformedResponse(format!(
+                    "no message content in response: {}",
+                    response_json
+                )))
+            }
+        };
+
+        let usage = &response_json["usage"];
+        suggestion.finish_reason = choice["finish_reason"]
             .as_str()
-            .unwrap_or("unknown-name")
+            .map(|reason| reason.to_string());
+        suggestion.usage = usage.is_object().then(|| Usage {
+            input_tokens: usage["prompt_tokens"].as_u64().unwrap_or(0),
+            output_tokens: usage["completion_tokens"].as_u64().unwrap_or(0),
+        });
+        Ok(suggestion)
+    }
+
+    // Names several downscaled images with one request. The answer has to
+    // hold exactly one name per index, anything else is a malformed response
+    // so the caller can fall back to one request per image.
+    pub async fn suggest_names(
+        &self,
+        image_paths: &[PathBuf],
+    ) -> Result<Vec<Suggestion>, ProviderError> {
+        info!(
+            "Getting names for {} images in one request",
+            image_paths.len()
+        );
+        let options = ImageOptions {
+            max_dimension: self.image.max_dimension.min(PACKED_MAX_DIMENSION),
+            detail: "low".to_string(),
+        };
+
+        // the prompt only has to be repeated when it renders per file
+        let prompts: Vec<String> = image_paths
+            .iter()
+            .map(|path| prompt::render(&self.prompt, path))
+            .collect();
+        let per_image = prompts.iter().any(|prompt| *prompt != prompts[0]);
+
+        let mut content = vec![];
+        if !per_image {
+            content.push(json!({ "type": "text", "text": prompts[0] }));
+        }
+        for (index, path) in image_paths.iter().enumerate() {
+            let image = EncodedImage::load(path, &options)?;
+            let text = match per_image {
+                true => format!("Image {}: {}", index, prompts[index]),
+                false => format!("Image {}:", index),
+            };
+            content.push(json!({ "type": "text", "text": text }));
+            content.push(json!({
+                "type": "image_url",
+                "image_url": { "url": image.data_url(), "detail": options.detail }
+            }));
+        }
+
+        let system_prompt = match self.structured {
+            true => PACKED_STRUCTURED_SYSTEM_PROMPT,
+            false => PACKED_SYSTEM_PROMPT,
+        };
+        let payload = json!({
+            "model": self.model,
+            "messages": [
+                { "role": "system", "content": system_prompt },
+                { "role": "user", "content": content }
+            ],
+        });
+
+        let response_text = self
+            .send(
+                self.request(reqwest::Method::POST, "chat/completions")
+                    .header(CONTENT_TYPE, "application/json")
+                    .body(payload.to_string()),
+            )
+            .await?;
+        let response_json: serde_json::Value = serde_json::from_str(&response_text)?;
+        self.parse_packed(&response_json, image_paths.len())
+    }
+
+    // what shapes a packed answer for this image, the detail level is fixed
+    // and the candidates don't apply
+    pub fn packed_fingerprint(&self, image_path: &PathBuf) -> String {
+        format!(
+            "openai-packed|{}|{}|{}|{}|{}",
+            self.base_url,
+            self.model,
+            self.structured,
+            self.image.max_dimension.min(PACKED_MAX_DIMENSION),
+            prompt::render(&self.prompt, image_path)
+        )
+    }
+
+    fn parse_packed(
+        &self,
+        response_json: &serde_json::Value,
+        images: usize,
+    ) -> Result<Vec<Suggestion>, ProviderError> {
+        let choice = &response_json["choices"][0];
+        if let Some(refusal) = choice["message"]["refusal"].as_str() {
+            return Err(ProviderError::ContentRefused(refusal.to_string()));
+        }
+        let content = choice["message"]["content"].as_str().ok_or_else(|| {
+            ProviderError::MalformedResponse(format!(
+                "no message content in response: {}",
+                response_json
+            ))
+        })?;
+
+        let content = content
             .trim()
-            .to_string();
+            .trim_start_matches("```json")
+            .trim_matches('`')
+            .trim();
+        let entries: Vec<serde_json::Value> = serde_json::from_str(content)?;
 
-        name
+        let mut suggestions: Vec<Option<Suggestion>> = vec![None; images];
+        for entry in entries.iter() {
+            let index = entry["index"].as_u64().map(|index| index as usize);
+            let slot = match index.and_then(|index| suggestions.get_mut(index)) {
+                Some(slot) if slot.is_none() => slot,
+                _ => {
+                    return Err(ProviderError::MalformedResponse(format!(
+                        "unexpected image index in response: {}",
+                        entry
+                    )))
+                }
+            };
+            let mut suggestion = Suggestion::parse(&entry.to_string(), true)?;
+            if !self.structured {
+                // plain mode only asks for the name
+                suggestion = Suggestion::new(suggestion.name);
+            }
+            *slot = Some(suggestion);
+        }
+
+        let mut suggestions = suggestions
+            .into_iter()
+            .collect::<Option<Vec<Suggestion>>>()
+            .ok_or_else(|| {
+                ProviderError::MalformedResponse(format!(
+                    "expected {} names, got {}",
+                    images,
+                    entries.len()
+                ))
+            })?;
+
+        // the request's tokens are spread evenly over its images
+        let usage = &response_json["usage"];
+        let share = |tokens: &serde_json::Value| tokens.as_u64().unwrap_or(0) / images as u64;
+        for suggestion in suggestions.iter_mut() {
+            suggestion.finish_reason = choice["finish_reason"]
+                .as_str()
+                .map(|reason| reason.to_string());
+            suggestion.usage = usage.is_object().then(|| Usage {
+                input_tokens: share(&usage["prompt_tokens"]),
+                output_tokens: share(&usage["completion_tokens"]),
+            });
+        }
+        Ok(suggestions)
+    }
+}
+
+#[async_trait]
+impl NamingProvider for OpenAI {
+    fn id(&self) -> &'static str {
+        "openai"
+    }
+
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    fn fingerprint(&self, image_path: &PathBuf) -> String {
+        format!(
+            "openai|{}|{}|{}|{}|{}|{}|{}",
+            self.base_url,
+            self.model,
+            self.structured,
+            self.image.max_dimension,
+            self.image.detail,
+            self.candidates,
+            prompt::render(&self.prompt, image_path)
+        )
+    }
+
+    async fn probe(&self) -> Result<(), anyhow::Error> {
+        let capabilities = self.probe_model().await?;
+        if !capabilities.available {
+            return Err(anyhow::anyhow!(
+                "Model {} is not served by {}",
+                self.model,
+                self.base_url
+            ));
+        }
+
+        match capabilities.vision {
+            Some(false) => Err(anyhow::anyhow!(
+                "Model {} does not support image input",
+                self.model
+            )),
+            Some(true) => Ok(()),
+            None => {
+                warn!(
+                    "Could not verify image support for model {} at {}",
+                    self.model, self.base_url
+                );
+                Ok(())
+            }
+        }
+    }
+
+    async fn suggest_name(&self, image_path: &PathBuf) -> Result<Suggestion, ProviderError> {
+        info!("Getting name for image: {:?}", image_path.display());
+        let payload = self.payload(image_path)?;
+
+        // Send the request to OpenAI API
+        self.make_ai_request(&payload).await
+    }
+
+    async fn suggest_distinct_name(
+        &self,
+        image_path: &PathBuf,
+        taken: &[String],
+    ) -> Result<Suggestion, ProviderError> {
+        info!("Getting another name for image: {:?}", image_path.display());
+        let text = prompt::distinct(&prompt::render(&self.prompt, image_path), taken);
+        let payload = self.payload_with_prompt(image_path, &text)?;
+        self.make_ai_request(&payload).await
     }
 }
@@ -0,0 +1,237 @@
+use async_trait::async_trait;
+use log::info;
+use reqwest::header::CONTENT_TYPE;
+use serde_json::json;
+use std::path::PathBuf;
+
+use crate::manager::{
+    encode::{EncodedImage, ImageOptions},
+    error::ProviderError,
+    http, prompt,
+    provider::{system_prompt, NamingProvider, Suggestion, Usage},
+};
+
+const MESSAGES_URL: &str = "https://api.anthropic.com/v1/messages";
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+// per name, a structured answer carries a description, tags and a category
+// next to it
+const MAX_TOKENS: u64 = 100;
+const STRUCTURED_MAX_TOKENS: u64 = 500;
+
+#[derive(Debug, Clone)]
+pub struct Anthropic {
+    api_key: String,
+    prompt: String,
+    model: String,
+    structured: bool,
+    image: ImageOptions,
+    // names asked for per image, the messages api has no `n` so they are
+    // requested in the prompt
+    candidates: u32,
+    client: reqwest::Client,
+}
+
+impl Anthropic {
+    pub fn new(
+        api_key: String,
+        prompt: String,
+        model: String,
+        structured: bool,
+        image: ImageOptions,
+        candidates: u32,
+    ) -> Self {
+        Self {
+            api_key,
+            prompt,
+            model,
+            structured,
+            image,
+            candidates,
+            client: http::client(),
+        }
+    }
+
+    fn max_tokens(&self) -> u64 {
+        let per_name = match self.structured {
+            true => STRUCTURED_MAX_TOKENS,
+            false => MAX_TOKENS,
+        };
+        per_name * self.candidates as u64
+    }
+
+    fn system_prompt(&self) -> String {
+        let prompt = system_prompt(self.structured);
+        match (self.candidates, self.structured) {
+            (1, _) => prompt.to_string(),
+            (n, false) => format!(
+                "{} Return {} different filenames, one per line, best first.",
+                prompt, n
+            ),
+            (n, true) => format!(
+                "{} Add \"alternatives\" with {} other distinct filenames in the same style.",
+                prompt,
+                n - 1
+            ),
+        }
+    }
+
+    // maps messages api stop reasons onto the openai finish reasons so callers
+    // only ever deal with one vocabulary
+    fn finish_reason(stop_reason: &str) -> String {
+        match stop_reason {
+            "end_turn" | "stop_sequence" => "stop",
+            "max_tokens" => "length",
+            "refusal" => "content_filter",
+            other => other,
+        }
+        .to_string()
+    }
+
+    async fn make_ai_request(
+        &self,
+        payload: &serde_json::Value,
+    ) -> Result<Suggestion, ProviderError> {
+        let response = self
+            .client
+            .post(MESSAGES_URL)
+            .header("x-api-key", &self.api_key)
+            .header("anthropic-version", ANTHROPIC_VERSION)
+            .header(CONTENT_TYPE, "application/json")
+            .body(payload.to_string())
+            .send()
+            .await?;
+
+        let status = response.status();
+        let headers = response.headers().clone();
+        let response_text = response.text().await?;
+
+        // error responses look like {"type": "error", "error": {"type": ..., "message": ...}}
+        if !status.is_success() {
+            let message = serde_json::from_str::<serde_json::Value>(&response_text)
+                .ok()
+                .and_then(|body| {
+                    let error = &body["error"];
+                    Some(format!(
+                        "{}: {}",
+                        error["type"].as_str()?,
+                        error["message"].as_str()?
+                    ))
+                })
+                .unwrap_or(response_text);
+            return Err(ProviderError::from_response(status, &headers, message));
+        }
+
+        let response_json: serde_json::Value = serde_json::from_str(&response_text)?;
+        if response_json["stop_reason"] == "refusal" {
+            return Err(ProviderError::ContentRefused(
+                "model refused to describe the image".to_string(),
+            ));
+        }
+
+        let mut suggestion = match response_json["content"]
+            .as_array()
+            .and_then(|blocks| blocks.iter().find(|block| block["type"] == "text"))
+            .and_then(|block| block["text"].as_str())
+        {
+            Some(text) if self.candidates > 1 => {
+                Suggestion::parse_candidates(text, self.structured)?
+            }
+            Some(text) => Suggestion::parse(text, self.structured)?,
+            None => {
+                return Err(ProviderError::MalformedResponse(format!(
+                    "no text block in response: {}",
+                    response_text
+                )))
+            }
+        };
+
+        let usage = &response_json["usage"];
+        suggestion.finish_reason = response_json["stop_reason"]
+            .as_str()
+            .map(Self::finish_reason);
+        suggestion.usage = usage.is_object().then(|| Usage {
+            input_tokens: usage["input_tokens"].as_u64().unwrap_or(0),
+            output_tokens: usage["output_tokens"].as_u64().unwrap_or(0),
+        });
+        Ok(suggestion)
+    }
+
+    fn payload(
+        &self,
+        image_path: &PathBuf,
+        text: &str,
+    ) -> Result<serde_json::Value, ProviderError> {
+        // Read the image file, re-encode it if needed and base64-encode it
+        let image = EncodedImage::load(image_path, &self.image)?;
+
+        // Create the JSON payload
+        let payload = json!({
+            "model": self.model,
+            "max_tokens": self.max_tokens(),
+            "system": self.system_prompt(),
+            "messages": [
+                {
+                    "role": "user",
+                    "content": [
+                        {
+                            "type": "image",
+                            "source": {
+                                "type": "base64",
+                                "media_type": image.mime(),
+                                "data": image.base64()
+                            }
+                        },
+                        {
+                            "type": "text",
+                            "text": text
+                        }
+                    ]
+                }
+            ],
+        });
+
+        Ok(payload)
+    }
+}
+
+#[async_trait]
+impl NamingProvider for Anthropic {
+    fn id(&self) -> &'static str {
+        "anthropic"
+    }
+
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    fn fingerprint(&self, image_path: &PathBuf) -> String {
+        format!(
+            "anthropic|{}|{}|{}|{}|{}|{}",
+            self.model,
+            self.structured,
+            self.image.max_dimension,
+            self.image.detail,
+            self.candidates,
+            prompt::render(&self.prompt, image_path)
+        )
+    }
+
+    async fn suggest_name(&self, image_path: &PathBuf) -> Result<Suggestion, ProviderError> {
+        info!("Getting name for image: {:?}", image_path.display());
+        let payload = self.payload(image_path, &prompt::render(&self.prompt, image_path))?;
+
+        // Send the request to Anthropic API
+        self.make_ai_request(&payload).await
+    }
+
+    async fn suggest_distinct_name(
+        &self,
+        image_path: &PathBuf,
+        taken: &[String],
+    ) -> Result<Suggestion, ProviderError> {
+        info!("Getting another name for image: {:?}", image_path.display());
+        let text = prompt::distinct(&prompt::render(&self.prompt, image_path), taken);
+        let payload = self.payload(image_path, &text)?;
+        self.make_ai_request(&payload).await
+    }
+}
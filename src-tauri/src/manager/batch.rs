@@ -0,0 +1,415 @@
+use std::{collections::HashMap, fs, path::PathBuf};
+
+use log::{error, info, warn};
+use reqwest::{header::CONTENT_TYPE, Method};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::{
+    manager::{
+        ai::OpenAI,
+        error::ProviderError,
+        image::{Renamed, SSManager},
+        ledger::Ledger,
+        provider::{NamingProvider, Suggestion},
+    },
+    utils::time,
+};
+
+const BATCH_ENDPOINT: &str = "/v1/chat/completions";
+const COMPLETION_WINDOW: &str = "24h";
+
+// batch states after which nothing will change any more
+const FINAL_STATES: [&str; 4] = ["completed", "failed", "expired", "cancelled"];
+
+// the batch api takes at most 50k requests and 200 MB per input file, the
+// byte limit leaves room for the multipart framing
+const MAX_REQUESTS_PER_BATCH: usize = 50_000;
+const MAX_BYTES_PER_BATCH: usize = 190 * 1024 * 1024;
+
+fn get_batches_path() -> PathBuf {
+    let batches_path = dirs::config_dir().unwrap().join("peeksy").join("batches");
+    if !batches_path.exists() {
+        std::fs::create_dir_all(&batches_path).unwrap();
+    }
+    batches_path
+}
+
+// The OpenAI batches of one submission, persisted in ~/.config/peeksy/batches
+// so the results can be applied by a later `peeksy batch-status` run. Large
+// runs are split over several batches, each one leaves the job once it is
+// applied or ended.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchJob {
+    // the id of the first batch, names the job file and the undo run
+    pub id: String,
+    pub profile: String,
+    pub model: String,
+    pub submitted_at: u64,
+    pub batches: Vec<Batch>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Batch {
+    pub id: String,
+    pub status: String,
+    // custom_id of each request line -> file it names
+    pub files: HashMap<String, String>,
+}
+
+impl BatchJob {
+    fn path(&self) -> PathBuf {
+        get_batches_path().join(format!("{}.json", self.id))
+    }
+
+    pub fn save(&self) -> Result<(), anyhow::Error> {
+        fs::write(self.path(), serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    pub fn remove(&self) {
+        if let Err(e) = fs::remove_file(self.path()) {
+            warn!("Failed to remove batch {}: {}", self.id, e);
+        }
+    }
+
+    pub fn images(&self) -> usize {
+        self.batches.iter().map(|batch| batch.files.len()).sum()
+    }
+
+    // every job that still has renames to apply
+    pub fn pending() -> Vec<BatchJob> {
+        let entries = match fs::read_dir(get_batches_path()) {
+            Ok(entries) => entries,
+            Err(_) => return vec![],
+        };
+
+        let mut jobs: Vec<BatchJob> = entries
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| fs::read_to_string(entry.path()).ok())
+            .filter_map(|content| serde_json::from_str(&content).ok())
+            .collect();
+        jobs.sort_by_key(|job| job.submitted_at);
+        jobs
+    }
+
+    // files already waiting in a batch, so a second run doesn't pay twice
+    pub fn pending_files() -> Vec<PathBuf> {
+        Self::pending()
+            .into_iter()
+            .flat_map(|job| job.batches)
+            .flat_map(|batch| batch.files.into_values())
+            .map(PathBuf::from)
+            .collect()
+    }
+}
+
+impl Batch {
+    pub fn is_final(&self) -> bool {
+        FINAL_STATES.contains(&self.status.as_str())
+    }
+}
+
+// files api uploads are multipart, built by hand like the json bodies
+async fn upload(openai: &OpenAI, jsonl: &str) -> Result<String, ProviderError> {
+    let boundary = format!("peeksy-batch-{}", time::unix_now());
+    let body = format!(
+        "--{b}\r\nContent-Disposition: form-data; name=\"purpose\"\r\n\r\nbatch\r\n\
+         --{b}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"peeksy-batch.jsonl\"\r\n\
+         Content-Type: application/jsonl\r\n\r\n{jsonl}\r\n--{b}--\r\n",
+        b = boundary,
+        jsonl = jsonl
+    );
+
+    let response = openai
+        .send(
+            openai
+                .request(Method::POST, "files")
+                .header(
+                    CONTENT_TYPE,
+                    format!("multipart/form-data; boundary={}", boundary),
+                )
+                .body(body),
+        )
+        .await?;
+    let file: serde_json::Value = serde_json::from_str(&response)?;
+    file["id"]
+        .as_str()
+        .map(|id| id.to_string())
+        .ok_or_else(|| ProviderError::MalformedResponse(format!("no file id in: {}", response)))
+}
+
+// request lines split into batch input files that stay within the limits
+fn split(
+    lines: Vec<(String, String, String)>,
+    max_bytes: usize,
+    max_requests: usize,
+) -> Vec<(String, HashMap<String, String>)> {
+    let mut chunks: Vec<(String, HashMap<String, String>)> = vec![];
+    for (custom_id, file, line) in lines {
+        // the line and its newline have to fit
+        let fits = chunks.last().is_some_and(|(jsonl, mapping)| {
+            mapping.len() < max_requests && jsonl.len() + line.len() < max_bytes
+        });
+        if !fits {
+            chunks.push((String::new(), HashMap::new()));
+        }
+        let (jsonl, mapping) = chunks.last_mut().unwrap();
+        jsonl.push_str(&line);
+        jsonl.push('\n');
+        mapping.insert(custom_id, file);
+    }
+    chunks
+}
+
+// uploads one input file and starts a batch over it
+async fn start(
+    openai: &OpenAI,
+    profile: &str,
+    jsonl: &str,
+) -> Result<(String, String), anyhow::Error> {
+    let file_id = upload(openai, jsonl).await?;
+    let response = openai
+        .send(
+            openai
+                .request(Method::POST, "batches")
+                .header(CONTENT_TYPE, "application/json")
+                .body(
+                    json!({
+                        "input_file_id": file_id,
+                        "endpoint": BATCH_ENDPOINT,
+                        "completion_window": COMPLETION_WINDOW,
+                        "metadata": { "source": "peeksy", "profile": profile }
+                    })
+                    .to_string(),
+                ),
+        )
+        .await?;
+    let batch: serde_json::Value = serde_json::from_str(&response)?;
+
+    let id = batch["id"]
+        .as_str()
+        .ok_or_else(|| anyhow::anyhow!("No batch id in response: {}", response))?;
+    let status = batch["status"].as_str().unwrap_or("validating");
+    Ok((id.to_string(), status.to_string()))
+}
+
+// builds one chat completion request per image, uploads them as jsonl files
+// and starts a batch over each. Files of a batch that fails to start are left
+// for the next run.
+pub async fn submit(
+    openai: &OpenAI,
+    profile: &str,
+    files: &[PathBuf],
+) -> Result<BatchJob, anyhow::Error> {
+    let mut lines = vec![];
+    for (index, file) in files.iter().enumerate() {
+        let payload = match openai.payload(file) {
+            Ok(payload) => payload,
+            Err(e) => {
+                error!("Skipping {:?}, failed to build request: {}", file, e);
+                continue;
+            }
+        };
+
+        let custom_id = format!("peeksy-{}", index);
+        let line = json!({
+            "custom_id": custom_id,
+            "method": "POST",
+            "url": BATCH_ENDPOINT,
+            "body": payload
+        });
+        lines.push((custom_id, file.display().to_string(), line.to_string()));
+    }
+
+    if lines.is_empty() {
+        return Err(anyhow::anyhow!("No requests to submit"));
+    }
+
+    let mut batches = vec![];
+    let mut error = None;
+    for (jsonl, files) in split(lines, MAX_BYTES_PER_BATCH, MAX_REQUESTS_PER_BATCH) {
+        match start(openai, profile, &jsonl).await {
+            Ok((id, status)) => {
+                info!("Submitted batch {} with {} images", id, files.len());
+                batches.push(Batch { id, status, files });
+            }
+            Err(e) => {
+                error!("Failed to submit batch of {} images: {:?}", files.len(), e);
+                error = Some(e);
+            }
+        }
+    }
+
+    let id = match batches.first() {
+        Some(batch) => batch.id.clone(),
+        None => return Err(error.unwrap_or_else(|| anyhow::anyhow!("No batch was submitted"))),
+    };
+    let job = BatchJob {
+        id,
+        profile: profile.to_string(),
+        model: openai.model().to_string(),
+        submitted_at: time::unix_now(),
+        batches,
+    };
+    job.save()?;
+    Ok(job)
+}
+
+// updates the batch's status and returns the output file once it is done
+pub async fn refresh(openai: &OpenAI, batch: &mut Batch) -> Result<Option<String>, anyhow::Error> {
+    let response = openai
+        .send(openai.request(Method::GET, &format!("batches/{}", batch.id)))
+        .await?;
+    let response: serde_json::Value = serde_json::from_str(&response)?;
+
+    batch.status = response["status"].as_str().unwrap_or("unknown").to_string();
+    if batch.status != "completed" {
+        return Ok(None);
+    }
+    Ok(response["output_file_id"].as_str().map(|id| id.to_string()))
+}
+
+// results by custom_id, each line either holds a chat completion or an error.
+// A line that can't be read only costs the file it belongs to.
+async fn results(
+    openai: &OpenAI,
+    output_file_id: &str,
+) -> Result<HashMap<String, Result<Suggestion, ProviderError>>, anyhow::Error> {
+    let content = openai
+        .send(openai.request(Method::GET, &format!("files/{}/content", output_file_id)))
+        .await?;
+
+    let mut results = HashMap::new();
+    for (number, line) in content.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let line: serde_json::Value = match serde_json::from_str(line) {
+            Ok(line) => line,
+            Err(e) => {
+                error!("Skipping batch result on line {}: {}", number + 1, e);
+                continue;
+            }
+        };
+        let custom_id = match line["custom_id"].as_str() {
+            Some(custom_id) => custom_id.to_string(),
+            None => {
+                error!(
+                    "Skipping batch result without custom_id on line {}",
+                    number + 1
+                );
+                continue;
+            }
+        };
+
+        let response = &line["response"];
+        let result = match response["status_code"].as_u64() {
+            Some(200) => openai.parse_completion(&response["body"]),
+            Some(status) => Err(ProviderError::Http {
+                status: status as u16,
+                message: response["body"]["error"]["message"]
+                    .as_str()
+                    .unwrap_or_default()
+                    .to_string(),
+            }),
+            None => Err(ProviderError::MalformedResponse(
+                line["error"]["message"]
+                    .as_str()
+                    .unwrap_or("no response")
+                    .to_string(),
+            )),
+        };
+        results.insert(custom_id, result);
+    }
+    Ok(results)
+}
+
+// applies the renames of a completed batch, files that moved or were renamed
+// in the meantime are left alone
+pub async fn apply(
+    openai: &OpenAI,
+    model: &str,
+    batch: &Batch,
+    output_file_id: &str,
+    ss_manager: &SSManager,
+    ledger: &Ledger,
+) -> Result<Vec<Renamed>, anyhow::Error> {
+    let mut results = results(openai, output_file_id).await?;
+    let mut renamed = vec![];
+
+    for (custom_id, file) in batch.files.iter() {
+        let path = PathBuf::from(file);
+        let result = match results.remove(custom_id) {
+            Some(result) => result,
+            None => {
+                warn!("No batch result for {:?}", path);
+                continue;
+            }
+        };
+
+        // every completed request is billed, whatever happens to the file
+        if let Ok(Some(usage)) = result.as_ref().map(|suggestion| suggestion.usage.as_ref()) {
+            if let Err(e) = ledger.record_batch(model, usage, &path) {
+                error!("Failed to record usage for {:?}: {}", path, e);
+            }
+        }
+
+        if !path.exists() {
+            warn!("Skipping {:?}, it no longer exists", path);
+            continue;
+        }
+
+        let suggestion = match ss_manager.accept(&path, result) {
+            Ok(Some(suggestion)) => suggestion,
+            Ok(None) => continue,
+            Err(e) => {
+                error!("Error processing file: {:?}, Error: {}", path, e);
+                continue;
+            }
+        };
+
+        match ss_manager.rename_random_image(&path, suggestion).await {
+            Ok(Some(done)) => renamed.push(done),
+            Ok(None) => {}
+            Err(e) => error!("Error processing file: {:?}", e),
+        }
+    }
+
+    Ok(renamed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(count: usize) -> Vec<(String, String, String)> {
+        (0..count)
+            .map(|index| {
+                let custom_id = format!("peeksy-{}", index);
+                (
+                    custom_id.clone(),
+                    format!("{}.png", index),
+                    format!("{{\"custom_id\":\"{}\"}}", custom_id),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn splits_by_request_count() {
+        let chunks = split(lines(5), usize::MAX, 2);
+        let sizes: Vec<usize> = chunks.iter().map(|(_, files)| files.len()).collect();
+        assert_eq!(sizes, vec![2, 2, 1]);
+        assert_eq!(chunks[2].0.lines().count(), 1);
+    }
+
+    #[test]
+    fn splits_by_size() {
+        // every line is 25 bytes with its newline
+        let chunks = split(lines(4), 60, usize::MAX);
+        let sizes: Vec<usize> = chunks.iter().map(|(_, files)| files.len()).collect();
+        assert_eq!(sizes, vec![2, 2]);
+        assert!(chunks.iter().all(|(jsonl, _)| jsonl.len() <= 60));
+    }
+}
@@ -0,0 +1,261 @@
+use std::{
+    fs,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+
+use async_trait::async_trait;
+use log::{info, warn};
+use sha2::{Digest, Sha256};
+
+use crate::manager::{
+    error::ProviderError,
+    provider::{NamingProvider, Suggestion},
+};
+
+const MAX_ENTRIES: usize = 5000;
+const MAX_AGE: Duration = Duration::from_secs(60 * 60 * 24 * 90);
+
+fn get_cache_path() -> PathBuf {
+    let cache_path = dirs::config_dir().unwrap().join("peeksy").join("cache");
+    if !cache_path.exists() {
+        std::fs::create_dir_all(&cache_path).unwrap();
+    }
+    cache_path
+}
+
+// Remembers suggestions by the sha256 of the image bytes and the provider
+// fingerprint, so re-running a bulk job or renaming a duplicate doesn't pay
+// for the same image twice. One file per entry keeps parallel `peeksy rename`
+// processes from the tray out of each other's way.
+#[derive(Debug)]
+pub struct ResponseCache {
+    inner: Arc<dyn NamingProvider>,
+    dir: PathBuf,
+    // entries on disk as of the last scan plus the ones written since, None
+    // until the first write of this run scans the directory
+    estimate: Mutex<Option<usize>>,
+}
+
+impl ResponseCache {
+    pub fn new(inner: Arc<dyn NamingProvider>) -> Self {
+        Self {
+            inner,
+            dir: get_cache_path(),
+            estimate: Mutex::new(None),
+        }
+    }
+
+    #[cfg(test)]
+    pub fn at(inner: Arc<dyn NamingProvider>, dir: PathBuf) -> Self {
+        Self {
+            inner,
+            dir,
+            estimate: Mutex::new(None),
+        }
+    }
+
+    pub fn wrap(inner: Arc<dyn NamingProvider>) -> Arc<dyn NamingProvider> {
+        Arc::new(Self::new(inner))
+    }
+
+    fn key(&self, image_path: &PathBuf, fingerprint: &str) -> Result<String, ProviderError> {
+        let mut hasher = Sha256::new();
+        hasher.update(fs::read(image_path)?);
+        hasher.update(fingerprint.as_bytes());
+        Ok(format!("{:x}", hasher.finalize()))
+    }
+
+    // For answers that don't come through `inner`, e.g. packed requests,
+    // kept under the fingerprint of the request that produced them.
+    pub fn lookup(&self, image_path: &PathBuf, fingerprint: &str) -> Option<Suggestion> {
+        let mut suggestion = self.get(&self.key(image_path, fingerprint).ok()?)?;
+        info!("Using cached name for image: {:?}", image_path.display());
+        // nothing was spent on this one
+        suggestion.usage = None;
+        Some(suggestion)
+    }
+
+    pub fn store(&self, image_path: &PathBuf, fingerprint: &str, suggestion: &Suggestion) {
+        if !cacheable(suggestion) {
+            return;
+        }
+        match self.key(image_path, fingerprint) {
+            Ok(key) => self.put(&key, suggestion),
+            Err(e) => warn!("Failed to cache name for {:?}: {}", image_path, e),
+        }
+    }
+
+    fn entry_path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", key))
+    }
+
+    fn get(&self, key: &str) -> Option<Suggestion> {
+        let path = self.entry_path(key);
+        let modified = fs::metadata(&path).and_then(|m| m.modified()).ok()?;
+        if SystemTime::now()
+            .duration_since(modified)
+            .unwrap_or_default()
+            > MAX_AGE
+        {
+            fs::remove_file(&path).ok();
+            return None;
+        }
+
+        // touching the entry on every hit turns MAX_AGE and the eviction
+        // order into least recently used
+        let file = fs::File::options()
+            .read(true)
+            .write(true)
+            .open(&path)
+            .ok()?;
+        file.set_modified(SystemTime::now()).ok();
+        serde_json::from_reader(file).ok()
+    }
+
+    fn put(&self, key: &str, suggestion: &Suggestion) {
+        let path = self.entry_path(key);
+        let temp_path = path.with_extension(format!("{}.tmp", std::process::id()));
+
+        let written = fs::File::create(&temp_path)
+            .map_err(anyhow::Error::from)
+            .and_then(|file| Ok(serde_json::to_writer(file, suggestion)?))
+            .and_then(|_| Ok(fs::rename(&temp_path, &path)?));
+        if let Err(e) = written {
+            warn!("Failed to write cache entry {:?}: {}", path, e);
+            fs::remove_file(&temp_path).ok();
+            return;
+        }
+
+        // the directory is only scanned once per run and whenever the
+        // estimate says it might be full
+        let mut estimate = self.estimate.lock().unwrap();
+        *estimate = match *estimate {
+            Some(count) if count < MAX_ENTRIES => Some(count + 1),
+            _ => Some(self.evict()),
+        };
+    }
+
+    // Drops expired entries and the oldest ones once the cache is over
+    // MAX_ENTRIES, returns how many are left.
+    fn evict(&self) -> usize {
+        let mut entries: Vec<(SystemTime, PathBuf)> = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries
+                .filter_map(|entry| entry.ok())
+                .filter_map(|entry| {
+                    let modified = entry.metadata().and_then(|m| m.modified()).ok()?;
+                    Some((modified, entry.path()))
+                })
+                .collect(),
+            Err(_) => return 0,
+        };
+
+        let now = SystemTime::now();
+        entries.retain(|(modified, path)| {
+            let expired = now.duration_since(*modified).unwrap_or_default() > MAX_AGE;
+            if expired {
+                fs::remove_file(path).ok();
+            }
+            !expired
+        });
+
+        if entries.len() <= MAX_ENTRIES {
+            return entries.len();
+        }
+
+        entries.sort_by_key(|(modified, _)| *modified);
+        let overflow = entries.len() - MAX_ENTRIES;
+        for (_, path) in entries.into_iter().take(overflow) {
+            fs::remove_file(path).ok();
+        }
+        MAX_ENTRIES
+    }
+}
+
+// refusals and truncated answers are worth asking again next time
+fn cacheable(suggestion: &Suggestion) -> bool {
+    let finish_reason = suggestion.finish_reason.as_deref();
+    finish_reason != Some("content_filter") && finish_reason != Some("length")
+}
+
+#[async_trait]
+impl NamingProvider for ResponseCache {
+    fn id(&self) -> &'static str {
+        self.inner.id()
+    }
+
+    fn model(&self) -> &str {
+        self.inner.model()
+    }
+
+    fn fingerprint(&self, image_path: &PathBuf) -> String {
+        self.inner.fingerprint(image_path)
+    }
+
+    async fn probe(&self) -> Result<(), anyhow::Error> {
+        self.inner.probe().await
+    }
+
+    async fn suggest_name(&self, image_path: &PathBuf) -> Result<Suggestion, ProviderError> {
+        let fingerprint = self.inner.fingerprint(image_path);
+        let key = self.key(image_path, &fingerprint)?;
+
+        if let Some(mut suggestion) = self.get(&key) {
+            info!("Using cached name for image: {:?}", image_path.display());
+            // nothing was spent on this one
+            suggestion.usage = None;
+            return Ok(suggestion);
+        }
+
+        let suggestion = self.inner.suggest_name(image_path).await?;
+        if cacheable(&suggestion) {
+            self.put(&key, &suggestion);
+        }
+        Ok(suggestion)
+    }
+
+    // the answer depends on the names in use right now, never cached
+    async fn suggest_distinct_name(
+        &self,
+        image_path: &PathBuf,
+        taken: &[String],
+    ) -> Result<Suggestion, ProviderError> {
+        self.inner.suggest_distinct_name(image_path, taken).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manager::{fake::Fake, provider::Usage};
+
+    #[test]
+    fn stores_answers_under_their_fingerprint() {
+        let dir = std::env::temp_dir().join(format!("peeksy-cache-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let image = dir.join("shot.png");
+        fs::write(&image, b"not really a png").unwrap();
+        let cache = ResponseCache::at(Arc::new(Fake::default()), dir.clone());
+
+        let mut suggestion = Suggestion::new("login-page".to_string());
+        suggestion.usage = Some(Usage {
+            input_tokens: 100,
+            output_tokens: 5,
+        });
+        cache.store(&image, "packed", &suggestion);
+
+        let cached = cache.lookup(&image, "packed").unwrap();
+        assert_eq!(cached.name, "login-page");
+        assert!(cached.usage.is_none());
+        assert!(cache.lookup(&image, "single").is_none());
+
+        // a truncated answer is asked for again
+        let mut truncated = Suggestion::new("login".to_string());
+        truncated.finish_reason = Some("length".to_string());
+        cache.store(&image, "truncated", &truncated);
+        assert!(cache.lookup(&image, "truncated").is_none());
+
+        fs::remove_dir_all(&dir).ok();
+    }
+}
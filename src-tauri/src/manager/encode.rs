@@ -0,0 +1,341 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    process::{self, Command},
+};
+
+use base64::Engine;
+use log::{info, warn};
+
+use crate::config::config::Config;
+
+const DEFAULT_MAX_DIMENSION: u32 = 1024;
+const DEFAULT_DETAIL: &str = "low";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    Png,
+    Jpeg,
+    Gif,
+    Webp,
+    Heic,
+    Tiff,
+    Bmp,
+    Unknown,
+}
+
+impl ImageFormat {
+    // sniffs the real format from the magic bytes, the extension of a file
+    // passed to `rename` can't be trusted
+    pub fn detect(bytes: &[u8]) -> Self {
+        match bytes {
+            [0x89, b'P', b'N', b'G', ..] => Self::Png,
+            [0xFF, 0xD8, 0xFF, ..] => Self::Jpeg,
+            [b'G', b'I', b'F', b'8', ..] => Self::Gif,
+            [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'E', b'B', b'P', ..] => Self::Webp,
+            [_, _, _, _, b'f', b't', b'y', b'p', brand @ ..] if brand.len() >= 4 => {
+                match &brand[..4] {
+                    b"heic" | b"heix" | b"hevc" | b"hevx" | b"heim" | b"heis" | b"mif1"
+                    | b"msf1" => Self::Heic,
+                    _ => Self::Unknown,
+                }
+            }
+            [b'I', b'I', 0x2A, 0x00, ..] | [b'M', b'M', 0x00, 0x2A, ..] => Self::Tiff,
+            [b'B', b'M', ..] => Self::Bmp,
+            _ => Self::Unknown,
+        }
+    }
+
+    pub fn mime(&self) -> &'static str {
+        match self {
+            Self::Png => "image/png",
+            Self::Jpeg => "image/jpeg",
+            Self::Gif => "image/gif",
+            Self::Webp => "image/webp",
+            Self::Heic => "image/heic",
+            Self::Tiff => "image/tiff",
+            Self::Bmp => "image/bmp",
+            Self::Unknown => "application/octet-stream",
+        }
+    }
+
+    // formats every provider accepts as is
+    pub fn is_supported(&self) -> bool {
+        matches!(self, Self::Png | Self::Jpeg | Self::Gif | Self::Webp)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ImageOptions {
+    // longest side in pixels before the image is downscaled
+    pub max_dimension: u32,
+    // openai vision detail level: low, high or auto
+    pub detail: String,
+}
+
+impl Default for ImageOptions {
+    fn default() -> Self {
+        Self {
+            max_dimension: DEFAULT_MAX_DIMENSION,
+            detail: DEFAULT_DETAIL.to_string(),
+        }
+    }
+}
+
+impl ImageOptions {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            max_dimension: config.max_image_dimension.unwrap_or(DEFAULT_MAX_DIMENSION),
+            detail: config
+                .image_detail
+                .clone()
+                .filter(|detail| !detail.is_empty())
+                .unwrap_or_else(|| DEFAULT_DETAIL.to_string()),
+        }
+    }
+}
+
+// Image bytes ready to upload, in a format every provider accepts and no
+// larger than `ImageOptions::max_dimension` on the longest side.
+#[derive(Debug, Clone)]
+pub struct EncodedImage {
+    pub format: ImageFormat,
+    pub data: Vec<u8>,
+}
+
+impl EncodedImage {
+    pub fn load(path: &PathBuf, options: &ImageOptions) -> Result<Self, io::Error> {
+        let data = fs::read(path)?;
+        let format = ImageFormat::detect(&data);
+
+        let too_large = match dimensions(&data, format, path) {
+            Some((width, height)) => width.max(height) > options.max_dimension,
+            None => false,
+        };
+
+        if format.is_supported() && !too_large {
+            return Ok(Self { format, data });
+        }
+
+        // screenshots compress better as png, photos and converted formats go to jpeg
+        let target = if format == ImageFormat::Png {
+            ImageFormat::Png
+        } else {
+            ImageFormat::Jpeg
+        };
+
+        let max_dimension = too_large.then_some(options.max_dimension);
+        match convert(path, target, max_dimension) {
+            Ok(data) => {
+                info!(
+                    "Re-encoded {:?} from {} to {}",
+                    path,
+                    format.mime(),
+                    target.mime()
+                );
+                Ok(Self {
+                    format: target,
+                    data,
+                })
+            }
+            // a large but supported image can still be sent as is
+            Err(e) if format.is_supported() => {
+                warn!("Failed to downscale {:?}, sending original: {}", path, e);
+                Ok(Self { format, data })
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn mime(&self) -> &'static str {
+        self.format.mime()
+    }
+
+    pub fn base64(&self) -> String {
+        base64::engine::general_purpose::STANDARD.encode(&self.data)
+    }
+
+    pub fn data_url(&self) -> String {
+        format!("data:{};base64,{}", self.mime(), self.base64())
+    }
+}
+
+// Pixel dimensions from the file header. Only formats that get converted
+// anyway are measured with sips.
+fn dimensions(data: &[u8], format: ImageFormat, path: &Path) -> Option<(u32, u32)> {
+    match format {
+        ImageFormat::Png => png_dimensions(data),
+        ImageFormat::Jpeg => jpeg_dimensions(data),
+        // the logical screen size follows the signature
+        ImageFormat::Gif => Some((le_u16(data, 6)?, le_u16(data, 8)?)),
+        ImageFormat::Webp => webp_dimensions(data),
+        _ => sips_dimensions(path),
+    }
+}
+
+fn be_u16(data: &[u8], at: usize) -> Option<u32> {
+    Some(u16::from_be_bytes(data.get(at..at + 2)?.try_into().ok()?) as u32)
+}
+
+fn le_u16(data: &[u8], at: usize) -> Option<u32> {
+    Some(u16::from_le_bytes(data.get(at..at + 2)?.try_into().ok()?) as u32)
+}
+
+fn le_u24(data: &[u8], at: usize) -> Option<u32> {
+    let bytes = data.get(at..at + 3)?;
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], 0]))
+}
+
+// width and height are the first fields of the IHDR chunk right after the
+// signature
+fn png_dimensions(data: &[u8]) -> Option<(u32, u32)> {
+    if data.get(12..16)? != b"IHDR" {
+        return None;
+    }
+    let width = u32::from_be_bytes(data.get(16..20)?.try_into().ok()?);
+    let height = u32::from_be_bytes(data.get(20..24)?.try_into().ok()?);
+    Some((width, height))
+}
+
+// walks the segments up to the first start of frame
+fn jpeg_dimensions(data: &[u8]) -> Option<(u32, u32)> {
+    let mut at = 2;
+    loop {
+        if *data.get(at)? != 0xFF {
+            return None;
+        }
+        let marker = *data.get(at + 1)?;
+        match marker {
+            // fill bytes before a marker
+            0xFF => at += 1,
+            // markers without a length
+            0x01 | 0xD0..=0xD7 => at += 2,
+            // every SOFn but DHT, JPG and DAC
+            0xC0..=0xCF if !matches!(marker, 0xC4 | 0xC8 | 0xCC) => {
+                return Some((be_u16(data, at + 7)?, be_u16(data, at + 5)?));
+            }
+            0xD9 | 0xDA => return None,
+            _ => at += 2 + be_u16(data, at + 2)? as usize,
+        }
+    }
+}
+
+// lossy, lossless and extended webp keep the canvas size in different places
+fn webp_dimensions(data: &[u8]) -> Option<(u32, u32)> {
+    match data.get(12..16)? {
+        b"VP8 " => Some((le_u16(data, 26)? & 0x3FFF, le_u16(data, 28)? & 0x3FFF)),
+        b"VP8L" => {
+            let bits = u32::from_le_bytes(data.get(21..25)?.try_into().ok()?);
+            Some(((bits & 0x3FFF) + 1, ((bits >> 14) & 0x3FFF) + 1))
+        }
+        b"VP8X" => Some((le_u24(data, 24)? + 1, le_u24(data, 27)? + 1)),
+        _ => None,
+    }
+}
+
+// reads pixel dimensions with sips, which ships with macOS
+fn sips_dimensions(path: &Path) -> Option<(u32, u32)> {
+    let output = Command::new("sips")
+        .args(["-g", "pixelWidth", "-g", "pixelHeight"])
+        .arg(path)
+        .output()
+        .ok()?;
+
+    if !output.status.success() {
+        return None;
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let value = |key: &str| {
+        stdout
+            .lines()
+            .find_map(|line| line.trim().strip_prefix(key))
+            .and_then(|value| value.trim().parse::<u32>().ok())
+    };
+    Some((value("pixelWidth:")?, value("pixelHeight:")?))
+}
+
+// converts and downscales with sips into a temp file, never touches the original
+fn convert(
+    path: &Path,
+    target: ImageFormat,
+    max_dimension: Option<u32>,
+) -> Result<Vec<u8>, io::Error> {
+    if !cfg!(target_os = "macos") {
+        return Err(io::Error::other(
+            "converting and downscaling images needs sips, which only ships with macOS",
+        ));
+    }
+
+    let (format, extension) = match target {
+        ImageFormat::Png => ("png", "png"),
+        _ => ("jpeg", "jpg"),
+    };
+
+    let temp_path = std::env::temp_dir().join(format!(
+        "peeksy-upload-{}-{}.{}",
+        process::id(),
+        path.file_stem()
+            .and_then(|stem| stem.to_str())
+            .unwrap_or("image")
+            .replace(' ', "-"),
+        extension
+    ));
+
+    let mut command = Command::new("sips");
+    command.args(["-s", "format", format]);
+    if let Some(max_dimension) = max_dimension {
+        command.args(["--resampleHeightWidthMax", &max_dimension.to_string()]);
+    }
+    let output = command.arg(path).arg("--out").arg(&temp_path).output()?;
+
+    if !output.status.success() {
+        return Err(io::Error::other(format!(
+            "sips failed on {:?}: {}",
+            path,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    let data = fs::read(&temp_path);
+    fs::remove_file(&temp_path).ok();
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn measure(data: &[u8]) -> Option<(u32, u32)> {
+        dimensions(data, ImageFormat::detect(data), Path::new("unused"))
+    }
+
+    #[test]
+    fn reads_png_size() {
+        let mut png = b"\x89PNG\r\n\x1a\n\x00\x00\x00\x0dIHDR".to_vec();
+        png.extend(2880u32.to_be_bytes());
+        png.extend(1800u32.to_be_bytes());
+        assert_eq!(measure(&png), Some((2880, 1800)));
+    }
+
+    #[test]
+    fn reads_jpeg_size() {
+        let mut jpeg = vec![0xFF, 0xD8];
+        // an app0 segment to skip, then a baseline start of frame
+        jpeg.extend([0xFF, 0xE0, 0x00, 0x04, 0x00, 0x00]);
+        jpeg.extend([0xFF, 0xC0, 0x00, 0x11, 0x08, 0x04, 0xB0, 0x06, 0x40]);
+        assert_eq!(measure(&jpeg), Some((1600, 1200)));
+
+        assert_eq!(measure(&[0xFF, 0xD8, 0xFF, 0xD9]), None);
+    }
+
+    #[test]
+    fn reads_gif_and_webp_size() {
+        let gif = b"GIF89a\x20\x03\x58\x02";
+        assert_eq!(measure(gif), Some((800, 600)));
+
+        let mut webp = b"RIFF\x00\x00\x00\x00WEBPVP8X\x0a\x00\x00\x00\x00\x00\x00\x00".to_vec();
+        webp.extend([0x7F, 0x07, 0x00, 0x37, 0x04, 0x00]);
+        assert_eq!(measure(&webp), Some((1920, 1080)));
+    }
+}
@@ -0,0 +1,175 @@
+use std::{fmt, io, time::Duration};
+
+use reqwest::{header::HeaderMap, header::RETRY_AFTER, StatusCode};
+
+#[derive(Debug)]
+pub enum ProviderError {
+    // key missing, invalid or expired
+    Auth(String),
+    RateLimited {
+        retry_after: Option<Duration>,
+        message: String,
+    },
+    // out of credits or billing limit hit, retrying won't help
+    QuotaExceeded(String),
+    // any other non success status
+    Http {
+        status: u16,
+        message: String,
+    },
+    // the request never got a response (dns, connection reset, offline)
+    Network(String),
+    MalformedResponse(String),
+    // the model refused or the content was filtered
+    ContentRefused(String),
+    Io(io::Error),
+    // the backend can't run on this machine, e.g. missing binary or config
+    Unavailable(String),
+    // replay mode has no recorded answer for this image, others may have one
+    NotRecorded(String),
+    // the daemon is shutting down or the user aborted the run
+    Cancelled,
+}
+
+impl ProviderError {
+    pub fn from_response(status: StatusCode, headers: &HeaderMap, message: String) -> Self {
+        let lowercase = message.to_lowercase();
+        let is_quota = lowercase.contains("quota")
+            || lowercase.contains("credit balance")
+            || lowercase.contains("billing");
+
+        match status.as_u16() {
+            401 | 403 => Self::Auth(message),
+            402 => Self::QuotaExceeded(message),
+            429 if is_quota => Self::QuotaExceeded(message),
+            429 => Self::RateLimited {
+                retry_after: Self::retry_after(headers),
+                message,
+            },
+            400 if is_quota => Self::QuotaExceeded(message),
+            status => Self::Http { status, message },
+        }
+    }
+
+    // Retry-After wins when present, otherwise wait for the slowest of the
+    // x-ratelimit-reset-* windows (e.g. "1s", "6m0s", "20ms")
+    fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+        let header = |name: &str| headers.get(name).and_then(|value| value.to_str().ok());
+
+        if let Some(ms) = header("retry-after-ms").and_then(|v| v.trim().parse::<f64>().ok()) {
+            return Some(Duration::from_secs_f64(ms / 1000.0));
+        }
+        if let Some(secs) = header(RETRY_AFTER.as_str()).and_then(|v| v.trim().parse::<f64>().ok())
+        {
+            return Some(Duration::from_secs_f64(secs));
+        }
+
+        ["x-ratelimit-reset-requests", "x-ratelimit-reset-tokens"]
+            .iter()
+            .filter_map(|name| header(name).and_then(Self::parse_reset))
+            .max()
+    }
+
+    fn parse_reset(value: &str) -> Option<Duration> {
+        let mut total = 0.0;
+        let mut number = String::new();
+        let mut chars = value.trim().chars().peekable();
+
+        while let Some(c) = chars.next() {
+            if c.is_ascii_digit() || c == '.' {
+                number.push(c);
+                continue;
+            }
+
+            let amount = number.parse::<f64>().ok()?;
+            number.clear();
+            total += match c {
+                'h' => amount * 3600.0,
+                'm' if chars.peek() == Some(&'s') => {
+                    chars.next();
+                    amount / 1000.0
+                }
+                'm' => amount * 60.0,
+                's' => amount,
+                _ => return None,
+            };
+        }
+
+        // a bare number is seconds
+        if !number.is_empty() {
+            total += number.parse::<f64>().ok()?;
+        }
+        Some(Duration::from_secs_f64(total))
+    }
+
+    // errors that may go away if the same request is sent again later
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Self::RateLimited { .. } | Self::Network(_) => true,
+            Self::Http { status, .. } => *status >= 500,
+            _ => false,
+        }
+    }
+
+    // errors that will fail every following request too, no point moving on
+    // to the next file
+    pub fn is_fatal(&self) -> bool {
+        matches!(
+            self,
+            Self::Auth(_) | Self::QuotaExceeded(_) | Self::Unavailable(_)
+        )
+    }
+}
+
+impl fmt::Display for ProviderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Auth(message) => write!(f, "Authentication failed: {}", message),
+            Self::RateLimited {
+                retry_after,
+                message,
+            } => match retry_after {
+                Some(retry_after) => write!(
+                    f,
+                    "Rate limited, retry after {:?}: {}",
+                    retry_after, message
+                ),
+                None => write!(f, "Rate limited: {}", message),
+            },
+            Self::QuotaExceeded(message) => write!(f, "Quota exceeded: {}", message),
+            Self::Http { status, message } => {
+                write!(f, "Request failed with status {}: {}", status, message)
+            }
+            Self::Network(message) => write!(f, "Network error: {}", message),
+            Self::MalformedResponse(message) => write!(f, "Malformed response: {}", message),
+            Self::ContentRefused(message) => write!(f, "Content refused: {}", message),
+            Self::Io(e) => write!(f, "I/O error: {}", e),
+            Self::Unavailable(message) => write!(f, "Provider unavailable: {}", message),
+            Self::NotRecorded(message) => write!(f, "Not recorded: {}", message),
+            Self::Cancelled => write!(f, "Request cancelled"),
+        }
+    }
+}
+
+impl std::error::Error for ProviderError {}
+
+impl From<io::Error> for ProviderError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<reqwest::Error> for ProviderError {
+    fn from(e: reqwest::Error) -> Self {
+        if e.is_timeout() {
+            return Self::Network(format!("request timed out: {}", e));
+        }
+        Self::Network(e.to_string())
+    }
+}
+
+impl From<serde_json::Error> for ProviderError {
+    fn from(e: serde_json::Error) -> Self {
+        Self::MalformedResponse(e.to_string())
+    }
+}
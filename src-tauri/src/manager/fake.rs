@@ -0,0 +1,105 @@
+use std::{collections::HashMap, fs, path::PathBuf};
+
+use async_trait::async_trait;
+use log::info;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    config::config::Config,
+    manager::{
+        error::ProviderError,
+        provider::{NamingProvider, Suggestion},
+    },
+};
+
+// Offline provider for trying out configs and rules: names come from a
+// fixture map or are derived from the image hash, so the same image always
+// gets the same name and nothing leaves the machine.
+#[derive(Debug, Clone, Default)]
+pub struct Fake {
+    // file name, file stem or sha256 of the image -> name or list of names
+    fixtures: HashMap<String, Vec<String>>,
+    candidates: u32,
+}
+
+impl Fake {
+    pub fn new(fixtures: HashMap<String, Vec<String>>, candidates: u32) -> Self {
+        Self {
+            fixtures,
+            candidates,
+        }
+    }
+
+    // fixtures are a json object, values are a name or a list of names:
+    // {"Screenshot 1.png": "login-page", "<sha256>": ["a", "b"]}
+    pub fn from_config(config: &Config) -> Result<Self, anyhow::Error> {
+        let path = match config.fake_fixtures_path.as_ref() {
+            Some(path) if !path.is_empty() => path,
+            _ => return Ok(Self::new(HashMap::new(), config.get_name_candidates())),
+        };
+
+        let content = fs::read_to_string(path).map_err(|e| {
+            anyhow::anyhow!("Failed to read fake fixtures: {:?}, Error: {}", path, e)
+        })?;
+        let raw: HashMap<String, serde_json::Value> = serde_json::from_str(&content)?;
+
+        let fixtures = raw
+            .into_iter()
+            .map(|(key, value)| {
+                let names = match value {
+                    serde_json::Value::Array(names) => names
+                        .iter()
+                        .filter_map(|name| name.as_str().map(|name| name.to_string()))
+                        .collect(),
+                    serde_json::Value::String(name) => vec![name],
+                    _ => vec![],
+                };
+                (key, names)
+            })
+            .filter(|(_, names)| !names.is_empty())
+            .collect();
+
+        Ok(Self::new(fixtures, config.get_name_candidates()))
+    }
+
+    fn fixture(&self, image_path: &PathBuf, hash: &str) -> Option<&Vec<String>> {
+        let name = image_path.file_name().and_then(|name| name.to_str());
+        let stem = image_path.file_stem().and_then(|stem| stem.to_str());
+        [name, stem, Some(hash)]
+            .into_iter()
+            .flatten()
+            .find_map(|key| self.fixtures.get(key.trim_start_matches('.')))
+    }
+}
+
+#[async_trait]
+impl NamingProvider for Fake {
+    fn id(&self) -> &'static str {
+        "fake"
+    }
+
+    fn model(&self) -> &str {
+        "fake"
+    }
+
+    async fn suggest_name(&self, image_path: &PathBuf) -> Result<Suggestion, ProviderError> {
+        let hash = format!("{:x}", Sha256::digest(fs::read(image_path)?));
+
+        let names = match self.fixture(image_path, &hash) {
+            Some(names) => names.clone(),
+            // 12 hex characters per candidate, 64 are enough for 5
+            None => (0..self.candidates as usize)
+                .map(|i| format!("fake-{}", &hash[i * 12..(i + 1) * 12]))
+                .collect(),
+        };
+        info!("Fake name for {:?}: {:?}", image_path.display(), names);
+
+        let mut names = names.into_iter();
+        Ok(Suggestion {
+            name: names.next().unwrap_or_default(),
+            alternatives: names.collect(),
+            finish_reason: Some("stop".to_string()),
+            ..Default::default()
+        })
+    }
+}
@@ -0,0 +1,341 @@
+use async_trait::async_trait;
+use log::{info, warn};
+use reqwest::header::CONTENT_TYPE;
+use serde_json::json;
+use std::path::PathBuf;
+
+use crate::manager::{
+    encode::{EncodedImage, ImageOptions},
+    error::ProviderError,
+    http, prompt,
+    provider::{suggestion_schema, system_prompt, NamingProvider, Suggestion, Usage},
+};
+
+pub const GEMINI_BASE_URL: &str = "https://generativelanguage.googleapis.com/v1beta/models";
+
+// finish reasons gemini uses when it refuses to answer
+const BLOCKED_FINISH_REASONS: [&str; 5] = [
+    "SAFETY",
+    "RECITATION",
+    "BLOCKLIST",
+    "PROHIBITED_CONTENT",
+    "SPII",
+];
+
+#[derive(Debug, Clone)]
+pub struct Gemini {
+    api_key: String,
+    prompt: String,
+    model: String,
+    structured: bool,
+    image: ImageOptions,
+    // names asked for per image, sent as `candidateCount`
+    candidates: u32,
+    client: reqwest::Client,
+}
+
+impl Gemini {
+    pub fn new(
+        api_key: String,
+        prompt: String,
+        model: String,
+        structured: bool,
+        image: ImageOptions,
+        candidates: u32,
+    ) -> Self {
+        Self {
+            api_key,
+            prompt,
+            model,
+            structured,
+            image,
+            candidates,
+            client: http::client(),
+        }
+    }
+
+    fn finish_reason(finish_reason: &str) -> String {
+        match finish_reason {
+            "STOP" => "stop".to_string(),
+            "MAX_TOKENS" => "length".to_string(),
+            reason if BLOCKED_FINISH_REASONS.contains(&reason) => "content_filter".to_string(),
+            other => other.to_lowercase(),
+        }
+    }
+
+    // a blocked image keeps its original name instead of becoming unknown-name
+    fn blocked(image_path: &PathBuf, reason: &str) -> Suggestion {
+        warn!(
+            "Gemini blocked image {:?} ({}), keeping original name",
+            image_path.display(),
+            reason
+        );
+        let name = image_path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .unwrap_or_default()
+            .to_string();
+
+        Suggestion {
+            name,
+            finish_reason: Some("content_filter".to_string()),
+            ..Default::default()
+        }
+    }
+
+    async fn make_ai_request(
+        &self,
+        image_path: &PathBuf,
+        payload: &serde_json::Value,
+    ) -> Result<Suggestion, ProviderError> {
+        let response = self
+            .client
+            .post(format!(
+                "{}/{}:generateContent",
+                GEMINI_BASE_URL, self.model
+            ))
+            .header("x-goog-api-key", &self.api_key)
+            .header(CONTENT_TYPE, "application/json")
+            .body(payload.to_string())
+            .send()
+            .await?;
+
+        let status = response.status();
+        let headers = response.headers().clone();
+        let response_text = response.text().await?;
+
+        if !status.is_success() {
+            let message = serde_json::from_str::<serde_json::Value>(&response_text)
+                .ok()
+                .and_then(|body| body["error"]["message"].as_str().map(|m| m.to_string()))
+                .unwrap_or(response_text);
+            return Err(ProviderError::from_response(status, &headers, message));
+        }
+
+        let response_json: serde_json::Value = serde_json::from_str(&response_text)?;
+        self.parse(image_path, &response_json)
+    }
+
+    fn parse(
+        &self,
+        image_path: &PathBuf,
+        response_json: &serde_json::Value,
+    ) -> Result<Suggestion, ProviderError> {
+        // the whole prompt was blocked, no candidates are returned
+        if let Some(reason) = response_json["promptFeedback"]["blockReason"].as_str() {
+            return Ok(Self::blocked(image_path, reason));
+        }
+
+        // with several candidates some may be blocked and others usable, the
+        // image only counts as blocked when none got through
+        let (blocked, allowed): (Vec<_>, Vec<_>) = response_json["candidates"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .map(|candidate| {
+                let reason = candidate["finishReason"].as_str().unwrap_or("STOP");
+                (candidate, reason)
+            })
+            .partition(|(_, reason)| BLOCKED_FINISH_REASONS.contains(reason));
+        if allowed.is_empty() {
+            if let Some((_, reason)) = blocked.first() {
+                return Ok(Self::blocked(image_path, reason));
+            }
+        }
+        let finish_reason = allowed.first().map_or("STOP", |(_, reason)| reason);
+
+        let choices = allowed
+            .iter()
+            .filter_map(|(candidate, _)| {
+                candidate["content"]["parts"]
+                    .as_array()?
+                    .iter()
+                    .find_map(|part| part["text"].as_str())
+            })
+            .filter_map(|text| Suggestion::parse(text, self.structured).ok())
+            .collect();
+
+        let mut suggestion = match Suggestion::merge(choices) {
+            Some(suggestion) => suggestion,
+            None => {
+                return Err(ProviderError::MalformedResponse(format!(
+                    "no text part in response: {}",
+                    response_json
+                )))
+            }
+        };
+
+        let usage = &response_json["usageMetadata"];
+        suggestion.finish_reason = Some(Self::finish_reason(finish_reason));
+        suggestion.usage = usage.is_object().then(|| Usage {
+            input_tokens: usage["promptTokenCount"].as_u64().unwrap_or(0),
+            output_tokens: usage["candidatesTokenCount"].as_u64().unwrap_or(0),
+        });
+        Ok(suggestion)
+    }
+
+    fn payload(
+        &self,
+        image_path: &PathBuf,
+        text: &str,
+    ) -> Result<serde_json::Value, ProviderError> {
+        // Read the image file, re-encode it if needed and base64-encode it
+        let image = EncodedImage::load(image_path, &self.image)?;
+
+        // Create the JSON payload
+        let mut payload = json!({
+            "systemInstruction": {
+                "parts": [{ "text": system_prompt(self.structured) }]
+            },
+            "contents": [
+                {
+                    "role": "user",
+                    "parts": [
+                        {
+                            "inline_data": {
+                                "mime_type": image.mime(),
+                                "data": image.base64()
+                            }
+                        },
+                        {
+                            "text": text
+                        }
+                    ]
+                }
+            ],
+        });
+
+        if self.structured {
+            payload["generationConfig"] = json!({
+                "responseMimeType": "application/json",
+                "responseSchema": gemini_schema(suggestion_schema())
+            });
+        }
+
+        if self.candidates > 1 {
+            payload["generationConfig"]["candidateCount"] = json!(self.candidates);
+        }
+
+        Ok(payload)
+    }
+}
+
+// Gemini takes an openapi subset: upper case types and no
+// `additionalProperties`
+fn gemini_schema(schema: serde_json::Value) -> serde_json::Value {
+    match schema {
+        serde_json::Value::Object(fields) => fields
+            .into_iter()
+            .filter(|(key, _)| key != "additionalProperties")
+            .map(|(key, value)| match (key.as_str(), value) {
+                ("type", serde_json::Value::String(kind)) => (key, json!(kind.to_uppercase())),
+                // property names are not schemas, their values are
+                ("properties", serde_json::Value::Object(properties)) => {
+                    let properties = properties
+                        .into_iter()
+                        .map(|(name, property)| (name, gemini_schema(property)))
+                        .collect();
+                    (key, serde_json::Value::Object(properties))
+                }
+                (_, value) => (key, gemini_schema(value)),
+            })
+            .collect(),
+        other => other,
+    }
+}
+
+#[async_trait]
+impl NamingProvider for Gemini {
+    fn id(&self) -> &'static str {
+        "gemini"
+    }
+
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    fn fingerprint(&self, image_path: &PathBuf) -> String {
+        format!(
+            "gemini|{}|{}|{}|{}|{}|{}",
+            self.model,
+            self.structured,
+            self.image.max_dimension,
+            self.image.detail,
+            self.candidates,
+            prompt::render(&self.prompt, image_path)
+        )
+    }
+
+    async fn suggest_name(&self, image_path: &PathBuf) -> Result<Suggestion, ProviderError> {
+        info!("Getting name for image: {:?}", image_path.display());
+        let payload = self.payload(image_path, &prompt::render(&self.prompt, image_path))?;
+
+        // Send the request to Gemini API
+        self.make_ai_request(image_path, &payload).await
+    }
+
+    async fn suggest_distinct_name(
+        &self,
+        image_path: &PathBuf,
+        taken: &[String],
+    ) -> Result<Suggestion, ProviderError> {
+        info!("Getting another name for image: {:?}", image_path.display());
+        let text = prompt::distinct(&prompt::render(&self.prompt, image_path), taken);
+        let payload = self.payload(image_path, &text)?;
+        self.make_ai_request(image_path, &payload).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gemini() -> Gemini {
+        let image = ImageOptions::default();
+        Gemini::new(String::new(), String::new(), String::new(), false, image, 2)
+    }
+
+    fn candidate(finish_reason: &str, text: &str) -> serde_json::Value {
+        json!({
+            "finishReason": finish_reason,
+            "content": { "parts": [{ "text": text }] }
+        })
+    }
+
+    #[test]
+    fn blocked_only_when_every_candidate_is() {
+        let image = PathBuf::from("/tmp/Screenshot 1.png");
+
+        let response = json!({
+            "candidates": [candidate("SAFETY", ""), candidate("STOP", "login-page")]
+        });
+        let suggestion = gemini().parse(&image, &response).unwrap();
+        assert_eq!(suggestion.name, "login-page");
+        assert_eq!(suggestion.finish_reason.as_deref(), Some("stop"));
+
+        let response = json!({
+            "candidates": [candidate("STOP", "login-page"), candidate("SAFETY", "")]
+        });
+        assert_eq!(
+            gemini().parse(&image, &response).unwrap().name,
+            "login-page"
+        );
+
+        let response = json!({
+            "candidates": [candidate("SAFETY", ""), candidate("SPII", "")]
+        });
+        let suggestion = gemini().parse(&image, &response).unwrap();
+        assert_eq!(suggestion.name, "Screenshot 1");
+        assert_eq!(suggestion.finish_reason.as_deref(), Some("content_filter"));
+    }
+
+    #[test]
+    fn schema_is_gemini_flavoured() {
+        let schema = gemini_schema(suggestion_schema());
+        assert_eq!(schema["type"], "OBJECT");
+        assert_eq!(schema["properties"]["tags"]["type"], "ARRAY");
+        assert_eq!(schema["properties"]["tags"]["items"]["type"], "STRING");
+        assert_eq!(schema["required"], suggestion_schema()["required"]);
+        assert!(schema.get("additionalProperties").is_none());
+    }
+}
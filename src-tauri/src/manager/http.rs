@@ -0,0 +1,214 @@
+use std::{fs, path::PathBuf, sync::OnceLock, time::Duration};
+
+use log::{error, info};
+
+use crate::config::config::Config;
+
+pub const DEFAULT_CONNECT_TIMEOUT_SECS: u64 = 10;
+pub const DEFAULT_READ_TIMEOUT_SECS: u64 = 60;
+
+static CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+
+#[derive(Debug, Clone)]
+pub struct ProxySettings {
+    pub url: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    // comma separated hosts or domains that bypass the proxy, * for all
+    pub no_proxy: Option<String>,
+}
+
+impl ProxySettings {
+    // the config wins over HTTPS_PROXY / ALL_PROXY / NO_PROXY from the
+    // environment, credentials can come from the config or be part of the url
+    pub fn from_config(config: &Config) -> Option<Self> {
+        let url = config
+            .https_proxy
+            .clone()
+            .filter(|url| !url.is_empty())
+            .or_else(|| env("HTTPS_PROXY"))
+            .or_else(|| env("ALL_PROXY"))?;
+
+        Some(Self {
+            url,
+            username: config.proxy_username.clone().filter(|u| !u.is_empty()),
+            password: config.proxy_password.clone(),
+            no_proxy: config
+                .no_proxy
+                .clone()
+                .filter(|hosts| !hosts.is_empty())
+                .or_else(|| env("NO_PROXY")),
+        })
+    }
+
+    pub fn bypasses(&self, host: &str) -> bool {
+        let host = host.to_lowercase();
+        self.no_proxy
+            .iter()
+            .flat_map(|hosts| hosts.split(','))
+            .map(|entry| entry.trim().trim_start_matches('.').to_lowercase())
+            .filter(|entry| !entry.is_empty())
+            .any(|entry| entry == "*" || host == entry || host.ends_with(&format!(".{}", entry)))
+    }
+
+    // the proxy url with the password masked, for logs and `peeksy doctor`
+    pub fn display(&self) -> String {
+        let url = match reqwest::Url::parse(&self.url) {
+            Ok(mut url) if url.password().is_some() => {
+                url.set_password(Some("****")).ok();
+                url.to_string()
+            }
+            _ => self.url.clone(),
+        };
+        match self.username.as_ref() {
+            Some(username) => format!("{} (as {})", url, username),
+            None => url,
+        }
+    }
+
+    fn build(&self) -> Result<reqwest::Proxy, anyhow::Error> {
+        let mut proxy = reqwest::Proxy::all(&self.url)?;
+        if let Some(username) = self.username.as_ref() {
+            proxy = proxy.basic_auth(username, self.password.as_deref().unwrap_or_default());
+        }
+        Ok(proxy.no_proxy(
+            self.no_proxy
+                .as_deref()
+                .and_then(reqwest::NoProxy::from_string),
+        ))
+    }
+}
+
+fn env(name: &str) -> Option<String> {
+    std::env::var(name)
+        .or_else(|_| std::env::var(name.to_lowercase()))
+        .ok()
+        .filter(|value| !value.is_empty())
+}
+
+#[derive(Debug, Clone)]
+pub struct HttpSettings {
+    pub connect_timeout: Duration,
+    pub read_timeout: Duration,
+    pub proxy: Option<ProxySettings>,
+    // pem file with extra root certificates, e.g. a corporate ca
+    pub ca_bundle: Option<PathBuf>,
+}
+
+impl HttpSettings {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            connect_timeout: Duration::from_secs(config.get_connect_timeout_secs()),
+            read_timeout: Duration::from_secs(config.get_read_timeout_secs()),
+            proxy: ProxySettings::from_config(config),
+            ca_bundle: config
+                .ca_bundle_path
+                .clone()
+                .filter(|path| !path.is_empty())
+                .map(PathBuf::from),
+        }
+    }
+
+    // reads the extra trust roots, a bundle may hold several certificates
+    pub fn certificates(&self) -> Result<Vec<reqwest::Certificate>, anyhow::Error> {
+        let path = match self.ca_bundle.as_ref() {
+            Some(path) => path,
+            None => return Ok(vec![]),
+        };
+
+        let pem = fs::read(path)
+            .map_err(|e| anyhow::anyhow!("Failed to read ca bundle: {:?}, Error: {}", path, e))?;
+        reqwest::Certificate::from_pem_bundle(&pem)
+            .map_err(|e| anyhow::anyhow!("Failed to parse ca bundle: {:?}, Error: {}", path, e))
+    }
+
+    pub fn build(&self) -> Result<reqwest::Client, anyhow::Error> {
+        let mut builder = reqwest::Client::builder()
+            .connect_timeout(self.connect_timeout)
+            .read_timeout(self.read_timeout);
+
+        // reqwest would pick up proxies from the environment on its own,
+        // only the ones in `proxy` are used so `peeksy doctor` tells the truth
+        match self.proxy.as_ref() {
+            Some(proxy) => {
+                info!("Using proxy {}", proxy.display());
+                let built = proxy
+                    .build()
+                    .map_err(|e| anyhow::anyhow!("Invalid proxy {}: {}", proxy.display(), e))?;
+                builder = builder.proxy(built);
+            }
+            None => builder = builder.no_proxy(),
+        }
+        for certificate in self.certificates()? {
+            builder = builder.add_root_certificate(certificate);
+        }
+
+        Ok(builder.build()?)
+    }
+}
+
+impl Default for HttpSettings {
+    fn default() -> Self {
+        Self {
+            connect_timeout: Duration::from_secs(DEFAULT_CONNECT_TIMEOUT_SECS),
+            read_timeout: Duration::from_secs(DEFAULT_READ_TIMEOUT_SECS),
+            proxy: None,
+            ca_bundle: None,
+        }
+    }
+}
+
+// One pooled client for every provider request in this process, so
+// connections and tls sessions are reused across images. The timeouts keep a
+// hung connection from blocking the daemon loop forever.
+// A proxy or ca bundle that can't be used is an error, sending around the
+// proxy or without the corporate ca would only fail later and less clearly.
+pub fn init(config: &Config) -> Result<(), anyhow::Error> {
+    if CLIENT.get().is_some() {
+        return Ok(());
+    }
+    let client = HttpSettings::from_config(config)
+        .build()
+        .map_err(|e| anyhow::anyhow!("Failed to setup http client: {}", e))?;
+    // another thread may have been first, its client is just as good
+    CLIENT.set(client).ok();
+    Ok(())
+}
+
+// the shared client, with default settings if `init` was never called
+pub fn client() -> reqwest::Client {
+    CLIENT
+        .get_or_init(|| {
+            HttpSettings::default().build().unwrap_or_else(|e| {
+                error!("Failed to build http client, using defaults: {:?}", e);
+                reqwest::Client::new()
+            })
+        })
+        .clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unusable_settings_are_errors() {
+        let settings = HttpSettings {
+            ca_bundle: Some(PathBuf::from("/nonexistent/peeksy-ca.pem")),
+            ..Default::default()
+        };
+        assert!(settings.build().is_err());
+
+        let settings = HttpSettings {
+            proxy: Some(ProxySettings {
+                url: "http://[proxy".to_string(),
+                username: None,
+                password: None,
+                no_proxy: None,
+            }),
+            ..Default::default()
+        };
+        let e = settings.build().unwrap_err();
+        assert!(e.to_string().starts_with("Invalid proxy"));
+    }
+}
@@ -1,17 +1,149 @@
+use std::fmt::Debug;
 use std::fs;
 use std::path::{Path, PathBuf};
-use std::time::{Duration, SystemTime};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-use crate::manager::ai::OpenAI;
+use log::{error, info, warn};
+
+use crate::{
+    config::config::Config,
+    manager::{
+        cancel::CancelToken,
+        error::ProviderError,
+        journal::{Journal, Origin},
+        metadata,
+        pack::Packer,
+        provider::{NamingProvider, Suggestion},
+        retry::RetryPolicy,
+    },
+    utils::{filename, mover, time},
+};
+
+// A finished rename, handed back so later steps can use the description,
+// tags and category from structured output mode.
+#[derive(Debug, Clone)]
+pub struct Renamed {
+    pub from: PathBuf,
+    pub to: PathBuf,
+    pub suggestion: Suggestion,
+}
+
+// numbered names tried before giving up on a taken name
+const MAX_SUFFIX: usize = 1000;
+
+// What to do when the suggested name is already used by another file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CollisionStrategy {
+    // login-page-2, login-page-3, ...
+    Suffix,
+    // login-page-20250131-142501, from the file's modification time
+    Timestamp,
+    // ask the model for a more specific name, numbers if that is taken too
+    Ask,
+    // keep the original name
+    Skip,
+}
+
+impl CollisionStrategy {
+    pub fn parse(strategy: &str) -> Option<Self> {
+        match strategy.to_lowercase().as_str() {
+            "suffix" => Some(Self::Suffix),
+            "timestamp" => Some(Self::Timestamp),
+            "ask" => Some(Self::Ask),
+            "skip" => Some(Self::Skip),
+            _ => None,
+        }
+    }
+
+    pub fn from_config(config: &Config) -> Self {
+        let strategy = config.get_collision_strategy();
+        Self::parse(&strategy).unwrap_or_else(|| {
+            warn!("Unknown collision strategy {}, using suffix", strategy);
+            Self::Suffix
+        })
+    }
+}
+
+// Lets the user choose between candidate names before a rename is
+// committed. Returning None keeps the original name.
+pub trait NamePicker: Debug + Send + Sync {
+    fn pick(&self, path: &PathBuf, candidates: &[String]) -> Option<String>;
+}
 
 #[derive(Debug, Clone)]
 pub struct SSManager {
-    ai: OpenAI,
+    ai: Arc<dyn NamingProvider>,
+    retry: RetryPolicy,
+    picker: Option<Arc<dyn NamePicker>>,
+    cancel: CancelToken,
+    collisions: CollisionStrategy,
+    journal: Journal,
+    // groups the renames of one bulk run in the journal
+    run: Option<String>,
+    // set on a dry run: targets already given out, nothing is moved
+    planned: Option<Arc<Mutex<Vec<PathBuf>>>>,
+    // write description and tags into the renamed file
+    embed_metadata: bool,
 }
 
 impl SSManager {
-    pub fn new(ai: OpenAI) -> Self {
-        Self { ai }
+    pub fn new(ai: Arc<dyn NamingProvider>, retry: RetryPolicy) -> Self {
+        Self {
+            ai,
+            retry,
+            picker: None,
+            cancel: CancelToken::new(),
+            collisions: CollisionStrategy::Suffix,
+            journal: Journal::default(),
+            run: None,
+            planned: None,
+            embed_metadata: false,
+        }
+    }
+
+    // resolves names and collisions as usual but leaves every file in place,
+    // the returned renames make up the plan
+    pub fn with_dry_run(mut self) -> Self {
+        self.planned = Some(Arc::new(Mutex::new(vec![])));
+        self
+    }
+
+    // renamed files get the description and tags from structured output
+    // written into their metadata
+    pub fn with_metadata(mut self, embed_metadata: bool) -> Self {
+        self.embed_metadata = embed_metadata;
+        self
+    }
+
+    #[cfg(test)]
+    pub fn with_journal(mut self, journal: Journal) -> Self {
+        self.journal = journal;
+        self
+    }
+
+    // every rename from here on is journaled as part of this run
+    pub fn with_run(mut self, run: String) -> Self {
+        self.run = Some(run);
+        self
+    }
+
+    // how a suggested name that is already in use gets resolved
+    pub fn with_collisions(mut self, collisions: CollisionStrategy) -> Self {
+        self.collisions = collisions;
+        self
+    }
+
+    // in-flight requests and retry waits stop as soon as the token is cancelled
+    pub fn with_cancel(mut self, cancel: CancelToken) -> Self {
+        self.cancel = cancel;
+        self
+    }
+
+    // only set for interactive renames, the daemon always takes the first name
+    pub fn with_picker(mut self, picker: Arc<dyn NamePicker>) -> Self {
+        self.picker = Some(picker);
+        self
     }
 
     fn modify_ss_path(&self, path: &PathBuf) -> PathBuf {
@@ -30,7 +162,7 @@ impl SSManager {
             }
             return (lowercase.starts_with("screenshot") || lowercase.contains("screen shot"))
                 && !lowercase.ends_with("-ss")
-                && path.extension().map_or(false, |ext| ext == "png");
+                && path.extension().is_some_and(|ext| ext == "png");
         }
         false
     }
@@ -47,41 +179,67 @@ impl SSManager {
         false
     }
 
-    fn delete_file(&self, path: &PathBuf) -> Result<(), anyhow::Error> {
-        if let Err(e) = fs::remove_file(path) {
-            return Err(anyhow::anyhow!(
-                "Failed to delete file: {:?}, Error: {}",
-                path,
-                e
-            ));
-        }
-        Ok(())
+    // a safe file stem in the naming style of the image's profile
+    pub fn clean_name(&self, path: &PathBuf, raw: &str) -> String {
+        filename::styled(&filename::sanitize(raw), self.ai.naming_style(path))
     }
 
-    async fn process_ss(&self, path: &PathBuf) -> Result<(), anyhow::Error> {
-        // create new filename
-        let mut new_filename = self.ai.get_name(&path).await;
-        new_filename += ".png";
-
-        // create new path
-        let parent = path.parent().unwrap_or(Path::new("."));
-        let new_path = parent.join(new_filename);
+    // Asks the provider for a name. Transient errors are retried by the retry
+    // policy, refused or unreadable images are skipped (None) and anything
+    // else leaves the file untouched and is handed back to the caller.
+    pub async fn get_name(&self, path: &PathBuf) -> Result<Option<Suggestion>, anyhow::Error> {
+        let result = tokio::select! {
+            result = self.retry.run(path, || self.ai.suggest_name(path)) => result,
+            _ = self.cancel.cancelled() => Err(ProviderError::Cancelled),
+        };
+        self.accept(path, result)
+    }
 
-        // copy file to new path
-        if let Err(e) = fs::copy(path.clone(), &new_path) {
-            return Err(anyhow::anyhow!(
-                "Failed to copy file: {:?} -> {:?}, Error: {}",
-                path.clone(),
-                new_path,
-                e
-            ));
+    // turns a provider answer into a usable suggestion, also used for
+    // answers that arrive later through the batch api
+    pub fn accept(
+        &self,
+        path: &PathBuf,
+        result: Result<Suggestion, ProviderError>,
+    ) -> Result<Option<Suggestion>, anyhow::Error> {
+        match result {
+            // blocked by the provider's safety filter, keep the original name
+            Ok(suggestion) if suggestion.finish_reason.as_deref() == Some("content_filter") => {
+                info!("Keeping original name for {:?}, content filtered", path);
+                Ok(None)
+            }
+            Ok(mut suggestion) => {
+                suggestion.name = self.clean_name(path, &suggestion.name);
+                suggestion.alternatives = suggestion
+                    .alternatives
+                    .iter()
+                    .map(|name| self.clean_name(path, name))
+                    .collect();
+                Ok(Some(suggestion))
+            }
+            Err(ProviderError::ContentRefused(reason)) => {
+                info!("Skipping {:?}, content refused: {}", path, reason);
+                Ok(None)
+            }
+            Err(ProviderError::MalformedResponse(reason)) => {
+                warn!("Skipping {:?}, no usable name: {}", path, reason);
+                Ok(None)
+            }
+            Err(e) => Err(e.into()),
         }
+    }
 
-        // delete old file
-        self.delete_file(&path)
+    async fn process_ss(&self, path: &PathBuf) -> Result<Option<Renamed>, anyhow::Error> {
+        // create new filename
+        let suggestion = match self.get_name(path).await? {
+            Some(suggestion) => suggestion,
+            None => return Ok(None),
+        };
+
+        self.commit(path, suggestion, "png").await
     }
 
-    pub async fn process_new_ss(&self, path: &PathBuf) -> Result<(), anyhow::Error> {
+    pub async fn process_new_ss(&self, path: &PathBuf) -> Result<Option<Renamed>, anyhow::Error> {
         if !self.is_screenshot_file(path) {
             return Err(anyhow::anyhow!(
                 "file is not screenshot or not recent: {:?}",
@@ -98,32 +256,485 @@ impl SSManager {
         self.process_ss(&path).await
     }
 
-    pub async fn process_random_image(&self, path: &PathBuf) -> Result<(), anyhow::Error> {
+    pub async fn process_random_image(
+        &self,
+        path: &PathBuf,
+    ) -> Result<Option<Renamed>, anyhow::Error> {
+        if path.extension().is_none() {
+            return Err(anyhow::anyhow!("Failed to get file extension"));
+        }
+
+        println!("Processing image: {:?}", path);
+        let mut suggestion = match self.get_name(path).await? {
+            Some(suggestion) => suggestion,
+            None => return Ok(None),
+        };
+
+        if let Some(picker) = self.picker.as_ref() {
+            let candidates = suggestion.candidates();
+            if candidates.len() > 1 {
+                match picker.pick(path, &candidates) {
+                    Some(name) => suggestion.name = self.clean_name(path, &name),
+                    None => {
+                        info!("Keeping original name for: {:?}", path);
+                        return Ok(None);
+                    }
+                }
+            }
+        }
+
+        self.rename_random_image(path, suggestion).await
+    }
+
+    // Names a group of images with one packed request. When the answer does
+    // not line up with the images every one of them is asked for on its own,
+    // any other error would hit the single requests just as well and is
+    // returned.
+    pub async fn process_packed(
+        &self,
+        packer: &Packer,
+        paths: &[PathBuf],
+    ) -> Result<Vec<Renamed>, anyhow::Error> {
+        info!("Processing {} images in one request", paths.len());
+        let result = tokio::select! {
+            result = self.retry.run(&paths[0], || packer.suggest_names(paths)) => result,
+            _ = self.cancel.cancelled() => Err(ProviderError::Cancelled),
+        };
+
+        let suggestions = match result {
+            Ok(suggestions) => suggestions,
+            Err(e @ ProviderError::MalformedResponse(_)) => {
+                warn!("Packed request failed, naming images one by one: {}", e);
+                let mut renamed = vec![];
+                for path in paths {
+                    match self.process_random_image(path).await {
+                        Ok(Some(done)) => renamed.push(done),
+                        Ok(None) => {}
+                        Err(e) => match e.downcast_ref::<ProviderError>() {
+                            Some(pe) if matches!(pe, ProviderError::Cancelled) || pe.is_fatal() => {
+                                return Err(e)
+                            }
+                            _ => error!("Error processing file: {:?}, Error: {}", path, e),
+                        },
+                    }
+                }
+                return Ok(renamed);
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut renamed = vec![];
+        for (path, suggestion) in paths.iter().zip(suggestions) {
+            let suggestion = match self.accept(path, Ok(suggestion))? {
+                Some(suggestion) => suggestion,
+                None => continue,
+            };
+            match self.rename_random_image(path, suggestion).await {
+                Ok(Some(done)) => renamed.push(done),
+                Ok(None) => {}
+                Err(e) => error!("Error processing file: {:?}", e),
+            }
+        }
+        Ok(renamed)
+    }
+
+    // commits a rename to an already chosen name, e.g. one picked in the app
+    pub async fn rename_random_image(
+        &self,
+        path: &PathBuf,
+        suggestion: Suggestion,
+    ) -> Result<Option<Renamed>, anyhow::Error> {
         let file_type = match path.extension() {
-            Some(ext) => ext.to_str().unwrap(),
+            Some(ext) => ext.to_str().unwrap().to_string(),
             None => return Err(anyhow::anyhow!("Failed to get file extension")),
         };
 
+        let renamed = self.commit(path, suggestion, &file_type).await?;
+        if let Some(renamed) = renamed.as_ref() {
+            println!("New filename: {:?}", renamed.to);
+        }
+        Ok(renamed)
+    }
+
+    // Moves the file to the suggested name. Existing files are never
+    // replaced, a taken name is resolved with the collision strategy.
+    async fn commit(
+        &self,
+        path: &PathBuf,
+        mut suggestion: Suggestion,
+        extension: &str,
+    ) -> Result<Option<Renamed>, anyhow::Error> {
         let parent = path.parent().unwrap_or(Path::new("."));
+        let target = |name: &str| parent.join(format!("{}.{}", name, extension));
 
-        println!("Processing image: {:?}", path);
-        let mut new_filename: String = self.ai.get_name(&path).await;
-        new_filename += &format!(".{}", file_type);
+        let mut taken = vec![];
+        let mut name = suggestion.name.clone();
+        loop {
+            let new_path = target(&name);
+            if new_path == *path {
+                info!("Keeping original name for: {:?}", path);
+                return Ok(None);
+            }
 
-        let new_path = parent.join(new_filename);
+            match self.place(path, &new_path, &suggestion) {
+                Ok(()) => {
+                    suggestion.name = name;
+                    return Ok(Some(Renamed {
+                        from: path.clone(),
+                        to: new_path,
+                        suggestion,
+                    }));
+                }
+                Err(e) if mover::is_taken(&e) => {
+                    info!("{:?} already exists", new_path);
+                    taken.push(name.clone());
+                }
+                Err(e) => return Err(e),
+            }
 
-        println!("New filename: {:?}", new_path);
+            name = match self.resolve_collision(path, &suggestion, &taken).await? {
+                Some(name) => name,
+                None => {
+                    info!("Skipping {:?}, {:?} is taken", path, suggestion.name);
+                    return Ok(None);
+                }
+            };
+        }
+    }
 
-        if let Err(e) = fs::copy(path.clone(), &new_path) {
-            return Err(anyhow::anyhow!(
-                "Failed to copy file: {:?} -> {:?}, Error: {}",
-                path,
-                new_path,
-                e
-            ));
+    // moves the file, or on a dry run only claims the target. Metadata goes
+    // in before journaling so the journaled hash is that of the final file.
+    fn place(
+        &self,
+        from: &PathBuf,
+        to: &PathBuf,
+        suggestion: &Suggestion,
+    ) -> Result<(), anyhow::Error> {
+        let planned = match self.planned.as_ref() {
+            Some(planned) => planned,
+            None => {
+                mover::move_file(from, to)?;
+                if self.embed_metadata {
+                    // the rename stands even when the metadata can't be written
+                    if let Err(e) = metadata::embed(to, suggestion) {
+                        error!("Failed to embed metadata into {:?}: {}", to, e);
+                    }
+                }
+                self.journal_rename(from, to);
+                return Ok(());
+            }
+        };
+
+        let mut planned = planned.lock().unwrap();
+        let claimed = planned.iter().any(|other| mover::same_name(other, to));
+        if claimed || !mover::is_free(from, to) {
+            return Err(mover::taken(to));
         }
+        planned.push(to.clone());
+        Ok(())
+    }
+
+    // profile, provider and model that name this file, and the current run
+    pub fn origin(&self, path: &PathBuf) -> Origin {
+        let (profile, provider, model) = self.ai.source(path);
+        Origin {
+            provider: provider.to_string(),
+            model: model.to_string(),
+            profile: profile.to_string(),
+            run: self.run.clone(),
+        }
+    }
+
+    // a rename that can't be journaled is still a rename, only logged
+    fn journal_rename(&self, from: &PathBuf, to: &PathBuf) {
+        if let Err(e) = self.journal.record(from, to, &self.origin(from), None) {
+            error!("Failed to journal rename {:?} -> {:?}: {}", from, to, e);
+        }
+    }
+
+    // the next name to try after every name in `taken` turned out to be in
+    // use, None gives up and keeps the original name
+    async fn resolve_collision(
+        &self,
+        path: &PathBuf,
+        suggestion: &Suggestion,
+        taken: &[String],
+    ) -> Result<Option<String>, anyhow::Error> {
+        let separator = match self.ai.naming_style(path) {
+            "snake" => '_',
+            _ => '-',
+        };
+        let base = &suggestion.name;
+        // the lowest number not tried yet, the original counts as the first
+        let suffixed = || {
+            (2..=MAX_SUFFIX)
+                .map(|n| format!("{}{}{}", base, separator, n))
+                .find(|name| !taken.contains(name))
+        };
+
+        match self.collisions {
+            CollisionStrategy::Skip => Ok(None),
+            CollisionStrategy::Suffix => Ok(suffixed()),
+            CollisionStrategy::Timestamp => match taken.len() {
+                1 => Ok(Some(format!(
+                    "{}{}{}",
+                    base,
+                    separator,
+                    time::compact(modified_secs(path))
+                ))),
+                // taken as well, e.g. two files from the same second
+                _ => Ok(suffixed()),
+            },
+            CollisionStrategy::Ask => {
+                // alternatives from the first answer cost nothing
+                if let Some(name) = suggestion
+                    .alternatives
+                    .iter()
+                    .find(|name| !taken.contains(name))
+                {
+                    return Ok(Some(name.clone()));
+                }
+
+                // the model gets one try, numbers after that
+                let asked = taken
+                    .iter()
+                    .any(|name| name != base && !suggestion.alternatives.contains(name));
+                if asked {
+                    return Ok(suffixed());
+                }
+                let result = tokio::select! {
+                    result = self.retry.run(path, || self.ai.suggest_distinct_name(path, taken)) => result,
+                    _ = self.cancel.cancelled() => Err(ProviderError::Cancelled),
+                };
+                match self.accept(path, result) {
+                    Ok(Some(distinct)) if !taken.contains(&distinct.name) => {
+                        Ok(Some(distinct.name))
+                    }
+                    Ok(_) => Ok(suffixed()),
+                    Err(e) => match e.downcast_ref::<ProviderError>() {
+                        Some(ProviderError::Cancelled) => Err(e),
+                        _ => {
+                            warn!("Failed to get another name for {:?}: {}", path, e);
+                            Ok(suffixed())
+                        }
+                    },
+                }
+            }
+        }
+    }
+}
+
+// seconds since the epoch the file was last modified, now if unknown
+fn modified_secs(path: &PathBuf) -> u64 {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .map(|modified| modified.as_secs())
+        .unwrap_or_else(time::unix_now)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    use async_trait::async_trait;
+
+    use super::*;
+
+    // answers with fixed names and counts how often it is asked again
+    #[derive(Debug)]
+    struct Names {
+        first: Suggestion,
+        distinct: &'static str,
+        asked: AtomicU32,
+    }
+
+    #[async_trait]
+    impl NamingProvider for Names {
+        fn id(&self) -> &'static str {
+            "names"
+        }
+
+        async fn suggest_name(&self, _image_path: &PathBuf) -> Result<Suggestion, ProviderError> {
+            Ok(self.first.clone())
+        }
+
+        async fn suggest_distinct_name(
+            &self,
+            _image_path: &PathBuf,
+            _taken: &[String],
+        ) -> Result<Suggestion, ProviderError> {
+            self.asked.fetch_add(1, Ordering::SeqCst);
+            Ok(Suggestion::new(self.distinct.to_string()))
+        }
+    }
+
+    struct Setup {
+        dir: PathBuf,
+        image: PathBuf,
+        names: Arc<Names>,
+        ss_manager: SSManager,
+    }
+
+    // a screenshot next to files that already use `taken` names
+    fn with_taken(
+        name: &str,
+        collisions: CollisionStrategy,
+        first: Suggestion,
+        taken: &[&str],
+    ) -> Setup {
+        let dir =
+            std::env::temp_dir().join(format!("peeksy-collide-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let image = dir.join("Screenshot 1.png");
+        fs::write(&image, b"screenshot").unwrap();
+        for name in taken {
+            fs::write(dir.join(format!("{}.png", name)), b"existing").unwrap();
+        }
+
+        let names = Arc::new(Names {
+            first,
+            distinct: "signup-form",
+            asked: AtomicU32::new(0),
+        });
+        let ss_manager = SSManager::new(names.clone(), RetryPolicy::new(1))
+            .with_collisions(collisions)
+            .with_journal(Journal::at(dir.join("journal.jsonl")));
+        Setup {
+            dir,
+            image,
+            names,
+            ss_manager,
+        }
+    }
+
+    fn login_page() -> Suggestion {
+        Suggestion::new("login-page".to_string())
+    }
+
+    async fn renamed_to(setup: &Setup) -> Option<PathBuf> {
+        let renamed = setup
+            .ss_manager
+            .process_random_image(&setup.image)
+            .await
+            .unwrap();
+        renamed.map(|renamed| renamed.to)
+    }
+
+    // every file that was there keeps its contents
+    fn assert_untouched(setup: &Setup, taken: &[&str]) {
+        for name in taken {
+            let path = setup.dir.join(format!("{}.png", name));
+            assert_eq!(fs::read(path).unwrap(), b"existing");
+        }
+    }
+
+    #[tokio::test]
+    async fn suffix_takes_the_next_free_number() {
+        let taken = ["login-page", "login-page-2"];
+        let setup = with_taken("suffix", CollisionStrategy::Suffix, login_page(), &taken);
+
+        let to = renamed_to(&setup).await.unwrap();
+        assert_eq!(to, setup.dir.join("login-page-3.png"));
+        assert_eq!(fs::read(&to).unwrap(), b"screenshot");
+        assert!(!setup.image.exists());
+        assert_untouched(&setup, &taken);
+
+        fs::remove_dir_all(&setup.dir).ok();
+    }
+
+    #[tokio::test]
+    async fn timestamp_adds_the_modification_time() {
+        let taken = ["login-page"];
+        let setup = with_taken(
+            "timestamp",
+            CollisionStrategy::Timestamp,
+            login_page(),
+            &taken,
+        );
+        let stamp = time::compact(modified_secs(&setup.image));
+
+        let to = renamed_to(&setup).await.unwrap();
+        assert_eq!(to, setup.dir.join(format!("login-page-{}.png", stamp)));
+        assert_untouched(&setup, &taken);
+
+        fs::remove_dir_all(&setup.dir).ok();
+    }
+
+    #[tokio::test]
+    async fn ask_tries_alternatives_then_the_model() {
+        let mut first = login_page();
+        first.alternatives = vec!["sign-in-page".to_string()];
+
+        // a free alternative costs no request
+        let setup_free = with_taken(
+            "ask-free",
+            CollisionStrategy::Ask,
+            first.clone(),
+            &["login-page"],
+        );
+        let to = renamed_to(&setup_free).await.unwrap();
+        assert_eq!(to, setup_free.dir.join("sign-in-page.png"));
+        assert_eq!(setup_free.names.asked.load(Ordering::SeqCst), 0);
+        fs::remove_dir_all(&setup_free.dir).ok();
+
+        // every alternative taken, the model is asked once
+        let taken = ["login-page", "sign-in-page"];
+        let setup = with_taken("ask", CollisionStrategy::Ask, first.clone(), &taken);
+        let to = renamed_to(&setup).await.unwrap();
+        assert_eq!(to, setup.dir.join("signup-form.png"));
+        assert_eq!(setup.names.asked.load(Ordering::SeqCst), 1);
+        assert_untouched(&setup, &taken);
+        fs::remove_dir_all(&setup.dir).ok();
+
+        // and numbers once its answer is taken too
+        let taken = ["login-page", "sign-in-page", "signup-form"];
+        let setup = with_taken("ask-taken", CollisionStrategy::Ask, first, &taken);
+        let to = renamed_to(&setup).await.unwrap();
+        assert_eq!(to, setup.dir.join("login-page-2.png"));
+        assert_eq!(setup.names.asked.load(Ordering::SeqCst), 1);
+        assert_untouched(&setup, &taken);
+        fs::remove_dir_all(&setup.dir).ok();
+    }
+
+    #[tokio::test]
+    async fn skip_keeps_the_original_name() {
+        let taken = ["login-page"];
+        let setup = with_taken("skip", CollisionStrategy::Skip, login_page(), &taken);
+
+        assert_eq!(renamed_to(&setup).await, None);
+        assert_eq!(fs::read(&setup.image).unwrap(), b"screenshot");
+        assert_untouched(&setup, &taken);
+
+        fs::remove_dir_all(&setup.dir).ok();
+    }
+
+    // two files of one plan never get the same target
+    #[tokio::test]
+    async fn dry_run_claims_targets() {
+        let setup = with_taken("dry-run", CollisionStrategy::Suffix, login_page(), &[]);
+        let second = setup.dir.join("Screenshot 2.png");
+        fs::write(&second, b"another screenshot").unwrap();
+        let ss_manager = setup.ss_manager.clone().with_dry_run();
+
+        let first = ss_manager
+            .process_random_image(&setup.image)
+            .await
+            .unwrap()
+            .unwrap();
+        let other = ss_manager
+            .process_random_image(&second)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(first.to, setup.dir.join("login-page.png"));
+        assert_eq!(other.to, setup.dir.join("login-page-2.png"));
+
+        // nothing moved
+        assert!(setup.image.exists() && second.exists());
+        assert!(!first.to.exists() && !other.to.exists());
 
-        self.delete_file(&path)
-            .map_err(|e| anyhow::anyhow!("Failed to delete file: {:?}, Error: {}", path, e))
+        fs::remove_dir_all(&setup.dir).ok();
     }
 }
@@ -0,0 +1,214 @@
+use std::{
+    collections::HashSet,
+    fs::{self, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::utils::{mover, time};
+
+fn get_journal_path() -> PathBuf {
+    let parent = dirs::config_dir().unwrap().join("peeksy");
+    if !parent.exists() {
+        std::fs::create_dir_all(&parent).unwrap();
+    }
+    parent.join("journal.jsonl")
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JournalEntry {
+    pub id: String,
+    pub timestamp: u64,
+    pub from: String,
+    pub to: String,
+    // sha256 of the file, an undo refuses to touch it once it changed
+    pub hash: String,
+    pub provider: String,
+    pub model: String,
+    pub profile: String,
+    // set for bulk and batch runs so they can be undone as a whole
+    pub run: Option<String>,
+    // the entry this one reverted
+    #[serde(default)]
+    pub undo_of: Option<String>,
+}
+
+// who named the file, kept with every rename
+#[derive(Debug, Clone, Default)]
+pub struct Origin {
+    pub provider: String,
+    pub model: String,
+    pub profile: String,
+    pub run: Option<String>,
+}
+
+// Append-only rename journal at ~/.config/peeksy/journal.jsonl, one json
+// line per rename. Undos are renames too and point at the entry they revert.
+#[derive(Debug, Clone)]
+pub struct Journal {
+    path: PathBuf,
+}
+
+impl Default for Journal {
+    fn default() -> Self {
+        Self {
+            path: get_journal_path(),
+        }
+    }
+}
+
+pub fn hash_file(path: &Path) -> Result<String, anyhow::Error> {
+    let bytes = fs::read(path)
+        .map_err(|e| anyhow::anyhow!("Failed to read file: {:?}, Error: {}", path, e))?;
+    Ok(format!("{:x}", Sha256::digest(bytes)))
+}
+
+// short and unique enough to type, derived from the rename itself
+fn entry_id(from: &Path, to: &Path) -> String {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|now| now.as_nanos())
+        .unwrap_or(0);
+    let mut hasher = Sha256::new();
+    hasher.update(nanos.to_le_bytes());
+    hasher.update(from.display().to_string().as_bytes());
+    hasher.update(to.display().to_string().as_bytes());
+    format!("{:x}", hasher.finalize())[..8].to_string()
+}
+
+// Absolute with symlinks resolved, so `history --dir` matches however the
+// folder was reached (/var vs /private/var on macOS). Only the directory is
+// resolved, the file itself may already be gone.
+pub fn resolve(path: &Path) -> PathBuf {
+    let absolute = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    match (absolute.parent(), absolute.file_name()) {
+        (Some(parent), Some(name)) => parent
+            .canonicalize()
+            .map(|parent| parent.join(name))
+            .unwrap_or(absolute),
+        _ => absolute,
+    }
+}
+
+// ids of the entries that have been reverted
+pub fn undone(entries: &[JournalEntry]) -> HashSet<String> {
+    entries
+        .iter()
+        .filter_map(|entry| entry.undo_of.clone())
+        .collect()
+}
+
+impl Journal {
+    #[cfg(test)]
+    pub fn at(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    pub fn entries(&self) -> Vec<JournalEntry> {
+        let file = match fs::File::open(&self.path) {
+            Ok(file) => file,
+            Err(_) => return vec![],
+        };
+
+        BufReader::new(file)
+            .lines()
+            .map_while(Result::ok)
+            .filter_map(|line| serde_json::from_str(&line).ok())
+            .collect()
+    }
+
+    pub fn record(
+        &self,
+        from: &Path,
+        to: &Path,
+        origin: &Origin,
+        undo_of: Option<&str>,
+    ) -> Result<JournalEntry, anyhow::Error> {
+        // relative paths from `peeksy rename` would break undo elsewhere
+        let (from, to) = (resolve(from), resolve(to));
+        let (from, to) = (from.as_path(), to.as_path());
+
+        let entry = JournalEntry {
+            id: entry_id(from, to),
+            timestamp: time::unix_now(),
+            from: from.display().to_string(),
+            to: to.display().to_string(),
+            hash: hash_file(to)?,
+            provider: origin.provider.clone(),
+            model: origin.model.clone(),
+            profile: origin.profile.clone(),
+            run: origin.run.clone(),
+            undo_of: undo_of.map(|id| id.to_string()),
+        };
+
+        // a single short write per line, so appends from the daemon and the
+        // cli don't interleave
+        let mut journal = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        journal.write_all(format!("{}\n", serde_json::to_string(&entry)?).as_bytes())?;
+        Ok(entry)
+    }
+
+    // Moves the file back to its old name. Refuses when the file is gone or
+    // its content changed since the rename, and never replaces a file that
+    // took the old name in the meantime.
+    pub fn undo(&self, entry: &JournalEntry) -> Result<JournalEntry, anyhow::Error> {
+        let (from, to) = (Path::new(&entry.from), Path::new(&entry.to));
+        if !to.exists() {
+            return Err(anyhow::anyhow!("{:?} no longer exists", to));
+        }
+        if hash_file(to)? != entry.hash {
+            return Err(anyhow::anyhow!("{:?} has changed since the rename", to));
+        }
+
+        mover::move_file(to, from).map_err(|e| match mover::is_taken(&e) {
+            true => anyhow::anyhow!("{:?} is taken by another file", from),
+            false => e,
+        })?;
+
+        let origin = Origin {
+            provider: entry.provider.clone(),
+            model: entry.model.clone(),
+            profile: entry.profile.clone(),
+            run: entry.run.clone(),
+        };
+        self.record(to, from, &origin, Some(&entry.id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // renames made through a symlinked folder are stored under its target
+    #[test]
+    fn records_resolved_paths() {
+        let dir = std::env::temp_dir().join(format!("peeksy-journal-{}", std::process::id()));
+        let real = dir.join("real");
+        let link = dir.join("link");
+        fs::create_dir_all(&real).unwrap();
+        std::os::unix::fs::symlink(&real, &link).unwrap();
+        fs::write(link.join("to.png"), b"image").unwrap();
+
+        let journal = Journal::at(dir.join("journal.jsonl"));
+        let entry = journal
+            .record(
+                &link.join("from.png"),
+                &link.join("to.png"),
+                &Origin::default(),
+                None,
+            )
+            .unwrap();
+        let real = real.canonicalize().unwrap();
+        assert_eq!(Path::new(&entry.from), real.join("from.png"));
+        assert_eq!(Path::new(&entry.to), real.join("to.png"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,492 @@
+use std::{
+    collections::HashMap,
+    fs::{self, OpenOptions},
+    io::{BufRead, BufReader, Read, Seek, SeekFrom, Write},
+    path::PathBuf,
+    process::Command,
+    sync::{Arc, Mutex, OnceLock},
+};
+
+use async_trait::async_trait;
+use log::{error, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::config::Config,
+    manager::{
+        error::ProviderError,
+        provider::{NamingProvider, Suggestion, Usage},
+    },
+    utils::time,
+};
+
+// used for estimates until the ledger has history for a model, roughly a
+// low detail image plus the default prompt
+const DEFAULT_INPUT_TOKENS: u64 = 450;
+const DEFAULT_OUTPUT_TOKENS: u64 = 20;
+
+pub const BATCH_RATE: f64 = 0.5;
+
+// usd per million input and output tokens, matched by model prefix so dated
+// snapshots (gpt-4o-2024-08-06) pick up their family's price. Longer
+// prefixes come first.
+const DEFAULT_PRICES: [(&str, f64, f64); 12] = [
+    ("gpt-4o-mini", 0.15, 0.60),
+    ("gpt-4o", 2.50, 10.00),
+    ("gpt-4.1-nano", 0.10, 0.40),
+    ("gpt-4.1-mini", 0.40, 1.60),
+    ("gpt-4.1", 2.00, 8.00),
+    ("claude-3-5-haiku", 0.80, 4.00),
+    ("claude-haiku-4-5", 1.00, 5.00),
+    ("claude-3-5-sonnet", 3.00, 15.00),
+    ("claude-sonnet-4", 3.00, 15.00),
+    ("gemini-2.0-flash", 0.10, 0.40),
+    ("gemini-2.5-flash", 0.30, 2.50),
+    ("gemini-2.5-pro", 1.25, 10.00),
+];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelPrice {
+    pub input_per_million: f64,
+    pub output_per_million: f64,
+}
+
+impl ModelPrice {
+    pub fn cost(&self, usage: &Usage) -> f64 {
+        (usage.input_tokens as f64 * self.input_per_million
+            + usage.output_tokens as f64 * self.output_per_million)
+            / 1_000_000.0
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LedgerEntry {
+    pub timestamp: u64,
+    pub seat: String,
+    pub provider: String,
+    pub model: String,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub cost_usd: f64,
+    pub file: String,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Totals {
+    pub requests: u64,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub cost_usd: f64,
+}
+
+impl Totals {
+    fn add(&mut self, entry: &LedgerEntry) {
+        self.requests += 1;
+        self.input_tokens += entry.input_tokens;
+        self.output_tokens += entry.output_tokens;
+        self.cost_usd += entry.cost_usd;
+    }
+}
+
+fn get_ledger_path() -> PathBuf {
+    let parent = dirs::config_dir().unwrap().join("peeksy");
+    if !parent.exists() {
+        std::fs::create_dir_all(&parent).unwrap();
+    }
+    parent.join("usage.jsonl")
+}
+
+// The seat is the local user, finance aggregates ledgers across machines.
+// launchd doesn't always set USER, so the daemon asks whoami and falls back
+// to the uid.
+pub fn get_seat() -> String {
+    static SEAT: OnceLock<String> = OnceLock::new();
+    SEAT.get_or_init(|| {
+        ["USER", "LOGNAME"]
+            .iter()
+            .filter_map(|name| std::env::var(name).ok())
+            .find(|user| !user.is_empty())
+            .or_else(|| {
+                Command::new("whoami")
+                    .output()
+                    .ok()
+                    .filter(|output| output.status.success())
+                    .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+                    .filter(|user| !user.is_empty())
+            })
+            .unwrap_or_else(|| format!("uid-{}", unsafe { libc::getuid() }))
+    })
+    .clone()
+}
+
+// Append-only token ledger at ~/.config/peeksy/usage.jsonl, one json line
+// per provider request.
+#[derive(Debug, Clone)]
+pub struct Ledger {
+    path: PathBuf,
+    prices: HashMap<String, ModelPrice>,
+}
+
+impl Ledger {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            path: get_ledger_path(),
+            prices: config.model_prices.clone().unwrap_or_default(),
+        }
+    }
+
+    #[cfg(test)]
+    pub fn at(path: PathBuf) -> Self {
+        Self {
+            path,
+            prices: HashMap::new(),
+        }
+    }
+
+    pub fn price(&self, model: &str) -> Option<ModelPrice> {
+        if let Some(price) = self.prices.get(model) {
+            return Some(price.clone());
+        }
+
+        DEFAULT_PRICES
+            .iter()
+            .find(|(prefix, _, _)| model.starts_with(prefix))
+            .map(|(_, input, output)| ModelPrice {
+                input_per_million: *input,
+                output_per_million: *output,
+            })
+    }
+
+    pub fn entries(&self) -> Vec<LedgerEntry> {
+        let file = match fs::File::open(&self.path) {
+            Ok(file) => file,
+            Err(_) => return vec![],
+        };
+
+        BufReader::new(file)
+            .lines()
+            .map_while(Result::ok)
+            .filter_map(|line| serde_json::from_str(&line).ok())
+            .collect()
+    }
+
+    pub fn record(
+        &self,
+        provider: &str,
+        model: &str,
+        usage: &Usage,
+        file: &PathBuf,
+    ) -> Result<LedgerEntry, anyhow::Error> {
+        self.record_at_rate(provider, model, usage, file, 1.0)
+    }
+
+    // batch api requests are billed at BATCH_RATE of the regular price
+    pub fn record_batch(
+        &self,
+        model: &str,
+        usage: &Usage,
+        file: &PathBuf,
+    ) -> Result<LedgerEntry, anyhow::Error> {
+        self.record_at_rate("openai-batch", model, usage, file, BATCH_RATE)
+    }
+
+    fn record_at_rate(
+        &self,
+        provider: &str,
+        model: &str,
+        usage: &Usage,
+        file: &PathBuf,
+        rate: f64,
+    ) -> Result<LedgerEntry, anyhow::Error> {
+        let cost_usd = match self.price(model) {
+            Some(price) => price.cost(usage) * rate,
+            None => {
+                warn!("No price known for model {}, recording zero cost. Add it to `model_prices` in the config", model);
+                0.0
+            }
+        };
+
+        let entry = LedgerEntry {
+            timestamp: time::unix_now(),
+            seat: get_seat(),
+            provider: provider.to_string(),
+            model: model.to_string(),
+            input_tokens: usage.input_tokens,
+            output_tokens: usage.output_tokens,
+            cost_usd,
+            file: file.display().to_string(),
+        };
+
+        // a single short write per line, so appends from parallel processes
+        // don't interleave
+        let mut ledger = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        ledger.write_all(format!("{}\n", serde_json::to_string(&entry)?).as_bytes())?;
+        Ok(entry)
+    }
+
+    // the entries of the lines appended after `offset`, and the offset past
+    // the last complete line
+    fn entries_from(&self, offset: u64) -> (Vec<LedgerEntry>, u64) {
+        let mut bytes = vec![];
+        let read = fs::File::open(&self.path).and_then(|mut file| {
+            file.seek(SeekFrom::Start(offset))?;
+            file.read_to_end(&mut bytes)
+        });
+        if read.is_err() {
+            return (vec![], offset);
+        }
+
+        // a line still being written is picked up next time
+        let complete = bytes
+            .iter()
+            .rposition(|b| *b == b'\n')
+            .map_or(0, |at| at + 1);
+        let entries = String::from_utf8_lossy(&bytes[..complete])
+            .lines()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect();
+        (entries, offset + complete as u64)
+    }
+
+    fn len(&self) -> u64 {
+        fs::metadata(&self.path).map_or(0, |metadata| metadata.len())
+    }
+
+    // totals for today and this month (utc) plus all time
+    pub fn totals(&self) -> (Totals, Totals, Totals) {
+        let now = time::unix_now();
+        let (today, this_month) = (time::date(now), time::month(now));
+
+        let mut totals = (Totals::default(), Totals::default(), Totals::default());
+        for entry in self.entries() {
+            if time::date(entry.timestamp) == today {
+                totals.0.add(&entry);
+            }
+            if time::month(entry.timestamp) == this_month {
+                totals.1.add(&entry);
+            }
+            totals.2.add(&entry);
+        }
+        totals
+    }
+
+    // average usage per request for a model from history, or a rough default
+    pub fn average_usage(&self, model: &str) -> Usage {
+        let entries: Vec<LedgerEntry> = self
+            .entries()
+            .into_iter()
+            .filter(|entry| entry.model == model)
+            .collect();
+
+        if entries.is_empty() {
+            return Usage {
+                input_tokens: DEFAULT_INPUT_TOKENS,
+                output_tokens: DEFAULT_OUTPUT_TOKENS,
+            };
+        }
+
+        let count = entries.len() as u64;
+        Usage {
+            input_tokens: entries.iter().map(|e| e.input_tokens).sum::<u64>() / count,
+            output_tokens: entries.iter().map(|e| e.output_tokens).sum::<u64>() / count,
+        }
+    }
+
+    pub fn estimate(&self, model: &str, images: usize) -> Option<f64> {
+        let price = self.price(model)?;
+        Some(price.cost(&self.average_usage(model)) * images as f64)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SpendingCaps {
+    pub daily_usd: Option<f64>,
+    pub monthly_usd: Option<f64>,
+    spent: Arc<Mutex<Spent>>,
+}
+
+// Today's and this month's spend as of `offset` in the ledger, so a check
+// only reads what was appended since the last one, also by other processes.
+#[derive(Debug, Default)]
+struct Spent {
+    today: String,
+    month: String,
+    offset: u64,
+    today_usd: f64,
+    month_usd: f64,
+}
+
+impl SpendingCaps {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            daily_usd: config.daily_spend_cap_usd,
+            monthly_usd: config.monthly_spend_cap_usd,
+            spent: Arc::default(),
+        }
+    }
+
+    // spend today and this month, the ledger is read in full only once a day
+    fn spent(&self, ledger: &Ledger) -> (f64, f64) {
+        let now = time::unix_now();
+        let (today, month) = (time::date(now), time::month(now));
+
+        let mut spent = self.spent.lock().unwrap();
+        // a new day or a ledger that was cut short starts over
+        if spent.today != today || ledger.len() < spent.offset {
+            *spent = Spent {
+                today,
+                month,
+                ..Default::default()
+            };
+        }
+
+        let (entries, offset) = ledger.entries_from(spent.offset);
+        for entry in entries {
+            if time::month(entry.timestamp) == spent.month {
+                spent.month_usd += entry.cost_usd;
+            }
+            if time::date(entry.timestamp) == spent.today {
+                spent.today_usd += entry.cost_usd;
+            }
+        }
+        spent.offset = offset;
+        (spent.today_usd, spent.month_usd)
+    }
+
+    pub fn check(&self, ledger: &Ledger) -> Result<(), ProviderError> {
+        if self.daily_usd.is_none() && self.monthly_usd.is_none() {
+            return Ok(());
+        }
+
+        let (today, this_month) = self.spent(ledger);
+        if let Some(cap) = self.daily_usd {
+            if today >= cap {
+                return Err(ProviderError::QuotaExceeded(format!(
+                    "daily spending cap of ${:.2} reached (${:.2} spent today)",
+                    cap, today
+                )));
+            }
+        }
+        if let Some(cap) = self.monthly_usd {
+            if this_month >= cap {
+                return Err(ProviderError::QuotaExceeded(format!(
+                    "monthly spending cap of ${:.2} reached (${:.2} spent this month)",
+                    cap, this_month
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+// Records the usage of every request in the ledger and refuses new requests
+// once a spending cap is hit, which pauses the daemon until the next day or
+// month.
+#[derive(Debug)]
+pub struct Metered {
+    inner: Arc<dyn NamingProvider>,
+    ledger: Ledger,
+    caps: SpendingCaps,
+}
+
+impl Metered {
+    pub fn wrap(inner: Arc<dyn NamingProvider>, config: &Config) -> Arc<dyn NamingProvider> {
+        Arc::new(Self {
+            inner,
+            ledger: Ledger::new(config),
+            caps: SpendingCaps::from_config(config),
+        })
+    }
+
+    fn meter(&self, image_path: &PathBuf, suggestion: Suggestion) -> Suggestion {
+        if let Some(usage) = suggestion.usage.as_ref() {
+            if let Err(e) =
+                self.ledger
+                    .record(self.inner.id(), self.inner.model(), usage, image_path)
+            {
+                error!("Failed to record usage for {:?}: {}", image_path, e);
+            }
+        }
+        suggestion
+    }
+}
+
+#[async_trait]
+impl NamingProvider for Metered {
+    fn id(&self) -> &'static str {
+        self.inner.id()
+    }
+
+    fn model(&self) -> &str {
+        self.inner.model()
+    }
+
+    fn fingerprint(&self, image_path: &PathBuf) -> String {
+        self.inner.fingerprint(image_path)
+    }
+
+    async fn probe(&self) -> Result<(), anyhow::Error> {
+        self.inner.probe().await
+    }
+
+    async fn suggest_name(&self, image_path: &PathBuf) -> Result<Suggestion, ProviderError> {
+        self.caps.check(&self.ledger)?;
+
+        let suggestion = self.inner.suggest_name(image_path).await?;
+        Ok(self.meter(image_path, suggestion))
+    }
+
+    async fn suggest_distinct_name(
+        &self,
+        image_path: &PathBuf,
+        taken: &[String],
+    ) -> Result<Suggestion, ProviderError> {
+        self.caps.check(&self.ledger)?;
+
+        let suggestion = self.inner.suggest_distinct_name(image_path, taken).await?;
+        Ok(self.meter(image_path, suggestion))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a million input tokens of gpt-4o
+    fn spend(ledger: &Ledger) {
+        let usage = Usage {
+            input_tokens: 1_000_000,
+            output_tokens: 0,
+        };
+        ledger
+            .record("openai", "gpt-4o", &usage, &PathBuf::from("shot.png"))
+            .unwrap();
+    }
+
+    #[test]
+    fn caps_catch_up_with_new_entries() {
+        let path = std::env::temp_dir().join(format!("peeksy-ledger-{}.jsonl", std::process::id()));
+        let ledger = Ledger::at(path.clone());
+        let caps = SpendingCaps {
+            daily_usd: Some(4.0),
+            ..Default::default()
+        };
+
+        spend(&ledger);
+        assert!(caps.check(&ledger).is_ok());
+        spend(&ledger);
+        assert!(matches!(
+            caps.check(&ledger),
+            Err(ProviderError::QuotaExceeded(_))
+        ));
+
+        // a ledger that was cleared is read again from the start
+        fs::write(&path, "").unwrap();
+        assert!(caps.check(&ledger).is_ok());
+
+        fs::remove_file(&path).ok();
+    }
+}
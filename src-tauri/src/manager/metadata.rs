@@ -0,0 +1,813 @@
+use std::{fs, path::Path};
+
+use log::{info, warn};
+
+use crate::{
+    manager::{encode::ImageFormat, provider::Suggestion},
+    utils::mover,
+};
+
+const PNG_SIGNATURE_LEN: usize = 8;
+const EXIF_HEADER: &[u8] = b"Exif\0\0";
+const XMP_NAMESPACE: &[u8] = b"http://ns.adobe.com/xap/1.0/\0";
+const PNG_XMP_KEYWORD: &str = "XML:com.adobe.xmp";
+// marks xmp packets peeksy wrote, anything else is left alone
+const XMP_TOOLKIT: &str = "x:xmptk=\"peeksy\"";
+const IMAGE_DESCRIPTION: u16 = 0x010E;
+const TIFF_ASCII: u16 = 2;
+// payload of a jpeg segment, after its marker and length
+const MAX_SEGMENT: usize = u16::MAX as usize - 2;
+
+// webp VP8X flags
+const WEBP_ALPHA: u8 = 0x10;
+const WEBP_EXIF: u8 = 0x08;
+const WEBP_XMP: u8 = 0x04;
+
+// Writes the description and tags from structured output into the image so
+// Finder, photo managers and DAMs can search them. Only metadata blocks are
+// added or swapped, the pixel data is copied byte for byte. Returns false
+// when there is nothing to write or the format has no place for it.
+pub fn embed(path: &Path, suggestion: &Suggestion) -> Result<bool, anyhow::Error> {
+    let description = suggestion
+        .description
+        .as_deref()
+        .map(|description| description.trim())
+        .filter(|description| !description.is_empty());
+    let tags = &suggestion.tags;
+    if description.is_none() && tags.is_empty() {
+        return Ok(false);
+    }
+
+    let bytes = fs::read(path)
+        .map_err(|e| anyhow::anyhow!("Failed to read file: {:?}, Error: {}", path, e))?;
+    let format = ImageFormat::detect(&bytes);
+    let malformed = || anyhow::anyhow!("Failed to parse image: {:?}", path);
+    let updated = match format {
+        ImageFormat::Png => png(&bytes, description, tags).ok_or_else(malformed)?,
+        ImageFormat::Jpeg => jpeg(&bytes, description, tags)?,
+        ImageFormat::Webp => webp(&bytes, description, tags).ok_or_else(malformed)?,
+        _ => {
+            info!(
+                "Not embedding metadata into {:?}, {} is not supported",
+                path,
+                format.mime()
+            );
+            return Ok(false);
+        }
+    };
+
+    mover::replace_contents(path, &updated)?;
+    info!("Embedded description and tags into {:?}", path);
+    Ok(true)
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+// dc:description and dc:subject, the fields photo tools search
+fn xmp_packet(description: Option<&str>, tags: &[String]) -> String {
+    let mut fields = String::new();
+    if let Some(description) = description {
+        fields.push_str(&format!(
+            "<dc:description><rdf:Alt><rdf:li xml:lang=\"x-default\">{}</rdf:li></rdf:Alt></dc:description>",
+            escape(description)
+        ));
+    }
+    if !tags.is_empty() {
+        fields.push_str("<dc:subject><rdf:Bag>");
+        for tag in tags {
+            fields.push_str(&format!("<rdf:li>{}</rdf:li>", escape(tag)));
+        }
+        fields.push_str("</rdf:Bag></dc:subject>");
+    }
+
+    format!(
+        "<?xpacket begin=\"\u{feff}\" id=\"W5M0MpCehiHzreSzNTczkc9d\"?>\
+         <x:xmpmeta xmlns:x=\"adobe:ns:meta/\" {}>\
+         <rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">\
+         <rdf:Description rdf:about=\"\" xmlns:dc=\"http://purl.org/dc/elements/1.1/\">{}</rdf:Description>\
+         </rdf:RDF></x:xmpmeta><?xpacket end=\"w\"?>",
+        XMP_TOOLKIT, fields
+    )
+}
+
+fn is_own_xmp(packet: &[u8]) -> bool {
+    String::from_utf8_lossy(packet).contains(XMP_TOOLKIT)
+}
+
+// Sets ImageDescription in a TIFF block (the body of an exif segment), or
+// builds a new block. IFD0 is copied to the end with the description added
+// and the header pointed at the copy, so every offset already in the block
+// stays valid. A copy made by an earlier embed is replaced rather than
+// stacked. None if the block can't be parsed.
+fn exif_with_description(tiff: Option<&[u8]>, description: &str) -> Option<Vec<u8>> {
+    let tiff = match tiff {
+        Some(tiff) => tiff,
+        // little endian header, IFD0 right after it with no entries
+        None => &[b'I', b'I', 0x2A, 0x00, 8, 0, 0, 0, 0, 0, 0, 0, 0, 0][..],
+    };
+
+    let little = match tiff.get(..2)? {
+        b"II" => true,
+        b"MM" => false,
+        _ => return None,
+    };
+    let read16 = |at: usize| -> Option<u16> {
+        let bytes: [u8; 2] = tiff.get(at..at + 2)?.try_into().ok()?;
+        Some(match little {
+            true => u16::from_le_bytes(bytes),
+            false => u16::from_be_bytes(bytes),
+        })
+    };
+    let read32 = |at: usize| -> Option<u32> {
+        let bytes: [u8; 4] = tiff.get(at..at + 4)?.try_into().ok()?;
+        Some(match little {
+            true => u32::from_le_bytes(bytes),
+            false => u32::from_be_bytes(bytes),
+        })
+    };
+    let bytes16 = |value: u16| match little {
+        true => value.to_le_bytes(),
+        false => value.to_be_bytes(),
+    };
+    let bytes32 = |value: u32| match little {
+        true => value.to_le_bytes(),
+        false => value.to_be_bytes(),
+    };
+
+    let ifd0 = read32(4)? as usize;
+    let count = read16(ifd0)? as usize;
+    let next_ifd = read32(ifd0 + 2 + count * 12)?;
+
+    // existing entries as raw 12 byte records, without an old description
+    let ifd0_end = ifd0 + 2 + count * 12 + 4;
+    let mut end = ifd0_end;
+    let mut entries: Vec<(u16, Vec<u8>)> = vec![];
+    for index in 0..count {
+        let at = ifd0 + 2 + index * 12;
+        let tag = read16(at)?;
+        if tag != IMAGE_DESCRIPTION {
+            entries.push((tag, tiff.get(at..at + 12)?.to_vec()));
+            continue;
+        }
+        let length = read32(at + 4)? as usize;
+        if length > 4 && read32(at + 8)? as usize == ifd0_end {
+            end = ifd0_end + length;
+        }
+    }
+
+    // an IFD0 that only its own description follows is the copy an earlier
+    // embed appended, nothing else points into it
+    let keep = match end == tiff.len() && ifd0 >= 8 {
+        true => ifd0,
+        false => tiff.len(),
+    };
+    let mut out = tiff[..keep].to_vec();
+    if out.len() % 2 == 1 {
+        out.push(0);
+    }
+    let new_ifd0 = out.len();
+    let mut value = description.as_bytes().to_vec();
+    value.push(0);
+    let value_offset = new_ifd0 + 2 + (entries.len() + 1) * 12 + 4;
+
+    let mut entry = vec![];
+    entry.extend_from_slice(&bytes16(IMAGE_DESCRIPTION));
+    entry.extend_from_slice(&bytes16(TIFF_ASCII));
+    entry.extend_from_slice(&bytes32(value.len() as u32));
+    if value.len() <= 4 {
+        let mut inline = value.clone();
+        inline.resize(4, 0);
+        entry.extend_from_slice(&inline);
+    } else {
+        entry.extend_from_slice(&bytes32(value_offset as u32));
+    }
+    entries.push((IMAGE_DESCRIPTION, entry));
+    // readers expect ascending tags
+    entries.sort_by_key(|(tag, _)| *tag);
+
+    out.extend_from_slice(&bytes16(entries.len() as u16));
+    for (_, entry) in entries.iter() {
+        out.extend_from_slice(entry);
+    }
+    out.extend_from_slice(&bytes32(next_ifd));
+    if value.len() > 4 {
+        out.extend_from_slice(&value);
+    }
+    out[4..8].copy_from_slice(&bytes32(new_ifd0 as u32));
+    Some(out)
+}
+
+// ---- png: tEXt / iTXt chunks before IEND ----
+
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for byte in bytes {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = match crc & 1 {
+                1 => (crc >> 1) ^ 0xEDB8_8320,
+                _ => crc >> 1,
+            };
+        }
+    }
+    !crc
+}
+
+fn png_chunk(kind: &[u8; 4], data: &[u8]) -> Vec<u8> {
+    let mut chunk = vec![];
+    chunk.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    chunk.extend_from_slice(kind);
+    chunk.extend_from_slice(data);
+    chunk.extend_from_slice(&crc32(&chunk[4..]).to_be_bytes());
+    chunk
+}
+
+// tEXt is latin-1, anything beyond ascii goes into an uncompressed iTXt
+fn png_text(keyword: &str, text: &str) -> Vec<u8> {
+    let mut data = keyword.as_bytes().to_vec();
+    data.push(0);
+    if text.is_ascii() {
+        data.extend_from_slice(text.as_bytes());
+        return png_chunk(b"tEXt", &data);
+    }
+    // compression flag and method, empty language tag and translated keyword
+    data.extend_from_slice(&[0, 0, 0, 0]);
+    data.extend_from_slice(text.as_bytes());
+    png_chunk(b"iTXt", &data)
+}
+
+fn png(bytes: &[u8], description: Option<&str>, tags: &[String]) -> Option<Vec<u8>> {
+    let mut chunks: Vec<&[u8]> = vec![];
+    let mut foreign_xmp = false;
+    let mut at = PNG_SIGNATURE_LEN;
+    while at < bytes.len() {
+        let length = u32::from_be_bytes(bytes.get(at..at + 4)?.try_into().ok()?) as usize;
+        let chunk = bytes.get(at..at + 12 + length)?;
+        let kind = &chunk[4..8];
+        let data = &chunk[8..8 + length];
+        at += 12 + length;
+
+        if kind == b"tEXt" || kind == b"iTXt" {
+            let keyword = data.split(|byte| *byte == 0).next().unwrap_or_default();
+            let ours = match keyword {
+                b"Description" | b"Keywords" => true,
+                keyword if keyword == PNG_XMP_KEYWORD.as_bytes() => {
+                    foreign_xmp = !is_own_xmp(data);
+                    !foreign_xmp
+                }
+                _ => false,
+            };
+            if ours {
+                continue;
+            }
+        }
+        if kind == b"IEND" {
+            break;
+        }
+        chunks.push(chunk);
+    }
+
+    let mut added = vec![];
+    if let Some(description) = description {
+        added.push(png_text("Description", description));
+    }
+    if !tags.is_empty() {
+        added.push(png_text("Keywords", &tags.join(", ")));
+    }
+    if foreign_xmp {
+        info!("Keeping the existing xmp packet, not adding dc:subject");
+    } else {
+        let mut data = PNG_XMP_KEYWORD.as_bytes().to_vec();
+        data.extend_from_slice(&[0, 0, 0, 0, 0]);
+        data.extend_from_slice(xmp_packet(description, tags).as_bytes());
+        added.push(png_chunk(b"iTXt", &data));
+    }
+
+    let mut out = bytes[..PNG_SIGNATURE_LEN].to_vec();
+    for chunk in chunks {
+        out.extend_from_slice(chunk);
+    }
+    for chunk in added {
+        out.extend_from_slice(&chunk);
+    }
+    out.extend_from_slice(&png_chunk(b"IEND", &[]));
+    Some(out)
+}
+
+// ---- jpeg: exif and xmp APP1 segments ----
+
+fn jpeg_app1(prefix: &[u8], body: &[u8]) -> Result<Vec<u8>, anyhow::Error> {
+    let length = prefix.len() + body.len();
+    if length > MAX_SEGMENT {
+        return Err(anyhow::anyhow!(
+            "Metadata too large for a jpeg segment: {} bytes, at most {}",
+            length,
+            MAX_SEGMENT
+        ));
+    }
+    let mut segment = vec![0xFF, 0xE1];
+    segment.extend_from_slice(&(length as u16 + 2).to_be_bytes());
+    segment.extend_from_slice(prefix);
+    segment.extend_from_slice(body);
+    Ok(segment)
+}
+
+// The segments before the scan and where the scan starts. Everything from
+// SOS on is image data and copied as is.
+fn jpeg_segments(bytes: &[u8]) -> Option<(Vec<Vec<u8>>, usize)> {
+    let mut segments = vec![];
+    let mut at = 2;
+    loop {
+        if *bytes.get(at)? != 0xFF {
+            return None;
+        }
+        let marker = *bytes.get(at + 1)?;
+        if marker == 0xFF {
+            // fill byte
+            at += 1;
+            continue;
+        }
+        if marker == 0xDA || marker == 0xD9 {
+            return Some((segments, at));
+        }
+        let length = u16::from_be_bytes(bytes.get(at + 2..at + 4)?.try_into().ok()?) as usize;
+        if length < 2 {
+            return None;
+        }
+        segments.push(bytes.get(at..at + 2 + length)?.to_vec());
+        at += 2 + length;
+    }
+}
+
+// Tags that don't fit into the one segment xmp gets are dropped from the
+// end, the description always fits.
+fn jpeg_xmp(description: Option<&str>, tags: &[String]) -> Result<Vec<u8>, anyhow::Error> {
+    let fits = |kept: usize| {
+        XMP_NAMESPACE.len() + xmp_packet(description, &tags[..kept]).len() <= MAX_SEGMENT
+    };
+    // the most tags that fit, searched for only when not all do
+    let mut kept = tags.len();
+    if !fits(kept) {
+        let mut low = 0;
+        while low < kept {
+            let middle = (low + kept).div_ceil(2);
+            match fits(middle) {
+                true => low = middle,
+                false => kept = middle - 1,
+            }
+        }
+        warn!(
+            "Only {} of {} tags fit into the jpeg xmp segment",
+            kept,
+            tags.len()
+        );
+    }
+    jpeg_app1(
+        XMP_NAMESPACE,
+        xmp_packet(description, &tags[..kept]).as_bytes(),
+    )
+}
+
+fn jpeg(
+    bytes: &[u8],
+    description: Option<&str>,
+    tags: &[String],
+) -> Result<Vec<u8>, anyhow::Error> {
+    let (mut segments, scan) =
+        jpeg_segments(bytes).ok_or_else(|| anyhow::anyhow!("Failed to parse jpeg segments"))?;
+    let app1 =
+        |segment: &Vec<u8>, prefix: &[u8]| segment[1] == 0xE1 && segment[4..].starts_with(prefix);
+    let mut exif_at = segments
+        .iter()
+        .position(|segment| app1(segment, EXIF_HEADER));
+    let xmp_at = segments
+        .iter()
+        .position(|segment| app1(segment, XMP_NAMESPACE));
+    let foreign_xmp = xmp_at.is_some_and(|index| !is_own_xmp(&segments[index][4..]));
+    // after the JFIF header, which has to stay first
+    let mut insert_at = segments
+        .iter()
+        .take_while(|segment| segment[1] == 0xE0)
+        .count();
+
+    if let Some(description) = description {
+        let tiff = exif_at.map(|index| &segments[index][4 + EXIF_HEADER.len()..]);
+        let tiff = exif_with_description(tiff, description)
+            .ok_or_else(|| anyhow::anyhow!("Failed to parse exif"))?;
+        let exif = jpeg_app1(EXIF_HEADER, &tiff)?;
+        match exif_at {
+            Some(index) => segments[index] = exif,
+            None => {
+                segments.insert(insert_at, exif);
+                exif_at = Some(insert_at);
+            }
+        }
+    }
+
+    if foreign_xmp {
+        info!("Keeping the existing xmp packet, not adding dc:subject");
+    } else {
+        let xmp = jpeg_xmp(description, tags)?;
+        // the exif segment may have been inserted before it
+        let xmp_at = segments
+            .iter()
+            .position(|segment| app1(segment, XMP_NAMESPACE));
+        match xmp_at {
+            Some(index) => segments[index] = xmp,
+            None => {
+                if let Some(index) = exif_at {
+                    insert_at = index + 1;
+                }
+                segments.insert(insert_at, xmp);
+            }
+        }
+    }
+
+    let mut out = bytes[..2].to_vec();
+    for segment in segments {
+        out.extend_from_slice(&segment);
+    }
+    out.extend_from_slice(&bytes[scan..]);
+    Ok(out)
+}
+
+// ---- webp: EXIF and XMP chunks, announced in VP8X ----
+
+fn webp_chunk(kind: &[u8; 4], data: &[u8]) -> Vec<u8> {
+    let mut chunk = kind.to_vec();
+    chunk.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    chunk.extend_from_slice(data);
+    if data.len() % 2 == 1 {
+        chunk.push(0);
+    }
+    chunk
+}
+
+// simple lossy and lossless files have no VP8X, it is built from the
+// bitstream's canvas size
+fn webp_vp8x(kind: &[u8], data: &[u8]) -> Option<Vec<u8>> {
+    let (width, height, flags) = match kind {
+        b"VP8 " => {
+            let width = u16::from_le_bytes(data.get(6..8)?.try_into().ok()?) & 0x3FFF;
+            let height = u16::from_le_bytes(data.get(8..10)?.try_into().ok()?) & 0x3FFF;
+            (width as u32, height as u32, 0)
+        }
+        b"VP8L" => {
+            let bits = u32::from_le_bytes(data.get(1..5)?.try_into().ok()?);
+            let alpha = match bits & (1 << 28) {
+                0 => 0,
+                _ => WEBP_ALPHA,
+            };
+            ((bits & 0x3FFF) + 1, ((bits >> 14) & 0x3FFF) + 1, alpha)
+        }
+        _ => return None,
+    };
+
+    let mut vp8x = vec![flags, 0, 0, 0];
+    vp8x.extend_from_slice(&width.checked_sub(1)?.to_le_bytes()[..3]);
+    vp8x.extend_from_slice(&height.checked_sub(1)?.to_le_bytes()[..3]);
+    Some(vp8x)
+}
+
+fn webp(bytes: &[u8], description: Option<&str>, tags: &[String]) -> Option<Vec<u8>> {
+    let mut vp8x: Option<Vec<u8>> = None;
+    let mut chunks: Vec<Vec<u8>> = vec![];
+    let mut exif: Option<Vec<u8>> = None;
+    let mut xmp: Option<Vec<u8>> = None;
+    let mut at = 12;
+    while at < bytes.len() {
+        let kind: [u8; 4] = bytes.get(at..at + 4)?.try_into().ok()?;
+        let length = u32::from_le_bytes(bytes.get(at + 4..at + 8)?.try_into().ok()?) as usize;
+        let data = bytes.get(at + 8..at + 8 + length)?;
+        at += 8 + length + length % 2;
+
+        match &kind {
+            b"VP8X" => vp8x = Some(data.to_vec()),
+            b"EXIF" => exif = Some(data.to_vec()),
+            b"XMP " => xmp = Some(data.to_vec()),
+            _ => {
+                if vp8x.is_none() && chunks.is_empty() {
+                    vp8x = Some(webp_vp8x(&kind, data)?);
+                }
+                chunks.push(webp_chunk(&kind, data));
+            }
+        }
+    }
+    let mut vp8x = vp8x?;
+
+    if let Some(description) = description {
+        let tiff = exif
+            .as_deref()
+            .map(|exif| match exif.starts_with(EXIF_HEADER) {
+                true => &exif[EXIF_HEADER.len()..],
+                false => exif,
+            });
+        exif = Some(exif_with_description(tiff, description)?);
+    }
+    match xmp.as_deref() {
+        Some(packet) if !is_own_xmp(packet) => {
+            info!("Keeping the existing xmp packet, not adding dc:subject")
+        }
+        _ => xmp = Some(xmp_packet(description, tags).into_bytes()),
+    }
+
+    if exif.is_some() {
+        vp8x[0] |= WEBP_EXIF;
+    }
+    if xmp.is_some() {
+        vp8x[0] |= WEBP_XMP;
+    }
+
+    let mut body = b"WEBP".to_vec();
+    body.extend_from_slice(&webp_chunk(b"VP8X", &vp8x));
+    for chunk in chunks {
+        body.extend_from_slice(&chunk);
+    }
+    if let Some(exif) = exif {
+        body.extend_from_slice(&webp_chunk(b"EXIF", &exif));
+    }
+    if let Some(xmp) = xmp {
+        body.extend_from_slice(&webp_chunk(b"XMP ", &xmp));
+    }
+
+    let mut out = b"RIFF".to_vec();
+    out.extend_from_slice(&(body.len() as u32).to_le_bytes());
+    out.extend_from_slice(&body);
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io::Cursor, path::PathBuf};
+
+    use image::{ImageFormat as Encoding, Rgb, RgbImage};
+
+    use super::*;
+
+    fn fixture(encoding: Encoding) -> Vec<u8> {
+        let image = RgbImage::from_fn(16, 12, |x, y| Rgb([x as u8 * 16, y as u8 * 20, 128]));
+        let mut bytes = Cursor::new(vec![]);
+        image.write_to(&mut bytes, encoding).unwrap();
+        bytes.into_inner()
+    }
+
+    fn write_fixture(name: &str, bytes: &[u8]) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("peeksy-metadata-{}-{}", std::process::id(), name));
+        fs::write(&path, bytes).unwrap();
+        path
+    }
+
+    fn suggestion(description: &str, tags: &[&str]) -> Suggestion {
+        Suggestion {
+            description: Some(description.to_string()),
+            tags: tags.iter().map(|tag| tag.to_string()).collect(),
+            ..Suggestion::new("name".to_string())
+        }
+    }
+
+    // the pixels are untouched, only metadata changed
+    fn assert_same_pixels(original: &[u8], embedded: &[u8]) {
+        let original = image::load_from_memory(original).unwrap().to_rgb8();
+        let embedded = image::load_from_memory(embedded).unwrap().to_rgb8();
+        assert_eq!(original, embedded);
+    }
+
+    fn exif_description(bytes: &[u8]) -> String {
+        let exif = exif::Reader::new()
+            .read_from_container(&mut Cursor::new(bytes))
+            .unwrap();
+        let field = exif
+            .get_field(exif::Tag::ImageDescription, exif::In::PRIMARY)
+            .unwrap();
+        match &field.value {
+            exif::Value::Ascii(values) => String::from_utf8(values[0].clone()).unwrap(),
+            value => panic!("ImageDescription is not ascii: {:?}", value),
+        }
+    }
+
+    fn xmp_subjects(bytes: &[u8]) -> Vec<String> {
+        let text = String::from_utf8_lossy(bytes);
+        assert_eq!(text.matches("<x:xmpmeta").count(), 1);
+        let bag = text
+            .split("<dc:subject><rdf:Bag>")
+            .nth(1)
+            .and_then(|rest| rest.split("</rdf:Bag>").next())
+            .unwrap();
+        bag.split("<rdf:li>")
+            .skip(1)
+            .map(|item| item.trim_end_matches("</rdf:li>").to_string())
+            .collect()
+    }
+
+    fn png_texts(bytes: &[u8]) -> Vec<(String, String)> {
+        let mut reader = png::Decoder::new(Cursor::new(bytes)).read_info().unwrap();
+        let mut pixels = vec![0; reader.output_buffer_size().unwrap()];
+        reader.next_frame(&mut pixels).unwrap();
+        reader.finish().unwrap();
+
+        let info = reader.info();
+        let latin1 = info
+            .uncompressed_latin1_text
+            .iter()
+            .map(|chunk| (chunk.keyword.clone(), chunk.text.clone()));
+        let utf8 = info
+            .utf8_text
+            .iter()
+            .map(|chunk| (chunk.keyword.clone(), chunk.get_text().unwrap()));
+        latin1.chain(utf8).collect()
+    }
+
+    fn png_text(texts: &[(String, String)], keyword: &str) -> Vec<String> {
+        texts
+            .iter()
+            .filter(|(key, _)| key == keyword)
+            .map(|(_, text)| text.clone())
+            .collect()
+    }
+
+    fn webp_chunks(bytes: &[u8]) -> Vec<([u8; 4], Vec<u8>)> {
+        assert_eq!(
+            u32::from_le_bytes(bytes[4..8].try_into().unwrap()) as usize,
+            bytes.len() - 8
+        );
+        let mut chunks = vec![];
+        let mut at = 12;
+        while at < bytes.len() {
+            let kind: [u8; 4] = bytes[at..at + 4].try_into().unwrap();
+            let length = u32::from_le_bytes(bytes[at + 4..at + 8].try_into().unwrap()) as usize;
+            chunks.push((kind, bytes[at + 8..at + 8 + length].to_vec()));
+            at += 8 + length + length % 2;
+        }
+        chunks
+    }
+
+    #[test]
+    fn png_round_trip() {
+        let original = fixture(Encoding::Png);
+        let path = write_fixture("round-trip.png", &original);
+
+        assert!(embed(&path, &suggestion("A login form", &["login", "form & ui"])).unwrap());
+        let first = fs::read(&path).unwrap();
+        assert_same_pixels(&original, &first);
+        let texts = png_texts(&first);
+        assert_eq!(png_text(&texts, "Description"), ["A login form"]);
+        assert_eq!(png_text(&texts, "Keywords"), ["login, form & ui"]);
+        let xmp = png_text(&texts, PNG_XMP_KEYWORD);
+        assert_eq!(xmp.len(), 1);
+        assert_eq!(xmp_subjects(xmp[0].as_bytes()), ["login", "form &amp; ui"]);
+
+        // non-ascii text goes into iTXt, the earlier chunks are replaced
+        assert!(embed(&path, &suggestion("Ein Anmeldeformular für Tests", &["ui"])).unwrap());
+        let second = fs::read(&path).unwrap();
+        assert_same_pixels(&original, &second);
+        let texts = png_texts(&second);
+        assert_eq!(
+            png_text(&texts, "Description"),
+            ["Ein Anmeldeformular für Tests"]
+        );
+        assert_eq!(png_text(&texts, "Keywords"), ["ui"]);
+        assert_eq!(png_text(&texts, PNG_XMP_KEYWORD).len(), 1);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn jpeg_round_trip() {
+        let original = fixture(Encoding::Jpeg);
+        let path = write_fixture("round-trip.jpg", &original);
+
+        assert!(embed(&path, &suggestion("A login form", &["login", "form"])).unwrap());
+        let first = fs::read(&path).unwrap();
+        assert_same_pixels(&original, &first);
+        assert_eq!(exif_description(&first), "A login form");
+        assert_eq!(xmp_subjects(&first), ["login", "form"]);
+
+        // the same suggestion again changes nothing
+        assert!(embed(&path, &suggestion("A login form", &["login", "form"])).unwrap());
+        assert_eq!(fs::read(&path).unwrap(), first);
+
+        assert!(embed(&path, &suggestion("A settings page", &["settings"])).unwrap());
+        let second = fs::read(&path).unwrap();
+        assert_same_pixels(&original, &second);
+        assert_eq!(exif_description(&second), "A settings page");
+        assert_eq!(xmp_subjects(&second), ["settings"]);
+        let (segments, _) = jpeg_segments(&second).unwrap();
+        let count = |prefix: &[u8]| {
+            segments
+                .iter()
+                .filter(|segment| segment[4..].starts_with(prefix))
+                .count()
+        };
+        assert_eq!(count(EXIF_HEADER), 1);
+        assert_eq!(count(XMP_NAMESPACE), 1);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn jpeg_keeps_existing_exif() {
+        // big endian block with an orientation and an old description
+        let mut tiff = b"MM\x00\x2a\x00\x00\x00\x08\x00\x02".to_vec();
+        tiff.extend_from_slice(b"\x01\x0e\x00\x02\x00\x00\x00\x04old\x00");
+        tiff.extend_from_slice(b"\x01\x12\x00\x03\x00\x00\x00\x01\x00\x06\x00\x00");
+        tiff.extend_from_slice(b"\x00\x00\x00\x00");
+        let original = fixture(Encoding::Jpeg);
+        let mut bytes = original[..2].to_vec();
+        bytes.extend_from_slice(&jpeg_app1(EXIF_HEADER, &tiff).unwrap());
+        bytes.extend_from_slice(&original[2..]);
+
+        let embedded = jpeg(&bytes, Some("A login form"), &[]).unwrap();
+        assert_same_pixels(&original, &embedded);
+        assert_eq!(exif_description(&embedded), "A login form");
+        let exif = exif::Reader::new()
+            .read_from_container(&mut Cursor::new(&embedded))
+            .unwrap();
+        let orientation = exif
+            .get_field(exif::Tag::Orientation, exif::In::PRIMARY)
+            .unwrap();
+        assert_eq!(orientation.value.get_uint(0), Some(6));
+    }
+
+    #[test]
+    fn jpeg_metadata_too_large() {
+        let original = fixture(Encoding::Jpeg);
+        let description = "a".repeat(70_000);
+        let e = jpeg(&original, Some(&description), &[]).unwrap_err();
+        assert!(e.to_string().contains("too large"), "{}", e);
+
+        // tags that don't fit are dropped instead
+        let tags: Vec<String> = (0..5_000).map(|index| format!("tag-{}", index)).collect();
+        let embedded = jpeg(&original, Some("A login form"), &tags).unwrap();
+        assert_same_pixels(&original, &embedded);
+        let subjects = xmp_subjects(&embedded);
+        assert!(!subjects.is_empty() && subjects.len() < tags.len());
+        assert_eq!(subjects[0], "tag-0");
+    }
+
+    #[test]
+    fn webp_round_trip() {
+        let original = fixture(Encoding::WebP);
+        let path = write_fixture("round-trip.webp", &original);
+
+        assert!(embed(&path, &suggestion("A login form", &["login", "form"])).unwrap());
+        let first = fs::read(&path).unwrap();
+        assert_same_pixels(&original, &first);
+        assert_eq!(exif_description(&first), "A login form");
+        assert_eq!(xmp_subjects(&first), ["login", "form"]);
+        let chunks = webp_chunks(&first);
+        assert_eq!(&chunks[0].0, b"VP8X");
+        assert_eq!(
+            chunks[0].1[0] & (WEBP_EXIF | WEBP_XMP),
+            WEBP_EXIF | WEBP_XMP
+        );
+        // canvas size is stored minus one
+        assert_eq!(&chunks[0].1[4..10], &[15, 0, 0, 11, 0, 0]);
+
+        assert!(embed(&path, &suggestion("A login form", &["login", "form"])).unwrap());
+        assert_eq!(fs::read(&path).unwrap(), first);
+
+        assert!(embed(&path, &suggestion("A settings page", &["settings"])).unwrap());
+        let second = fs::read(&path).unwrap();
+        assert_same_pixels(&original, &second);
+        assert_eq!(exif_description(&second), "A settings page");
+        assert_eq!(xmp_subjects(&second), ["settings"]);
+        let kinds: Vec<[u8; 4]> = webp_chunks(&second)
+            .into_iter()
+            .map(|(kind, _)| kind)
+            .collect();
+        assert_eq!(kinds, [*b"VP8X", *b"VP8L", *b"EXIF", *b"XMP "]);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn foreign_xmp_is_kept() {
+        let original = fixture(Encoding::Png);
+        let foreign = "<x:xmpmeta xmlns:x=\"adobe:ns:meta/\" x:xmptk=\"Other\"></x:xmpmeta>";
+        let mut data = PNG_XMP_KEYWORD.as_bytes().to_vec();
+        data.extend_from_slice(&[0, 0, 0, 0, 0]);
+        data.extend_from_slice(foreign.as_bytes());
+        let iend = original.len() - 12;
+        let mut bytes = original[..iend].to_vec();
+        bytes.extend_from_slice(&png_chunk(b"iTXt", &data));
+        bytes.extend_from_slice(&original[iend..]);
+
+        let embedded = png(&bytes, Some("A login form"), &["login".to_string()]).unwrap();
+        let texts = png_texts(&embedded);
+        assert_eq!(png_text(&texts, PNG_XMP_KEYWORD), [foreign]);
+        assert_eq!(png_text(&texts, "Keywords"), ["login"]);
+    }
+
+    #[test]
+    fn nothing_to_embed() {
+        let path = write_fixture("nothing.png", &fixture(Encoding::Png));
+        assert!(!embed(&path, &Suggestion::new("name".to_string())).unwrap());
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -1,2 +1,22 @@
 pub mod ai;
+pub mod anthropic;
+pub mod batch;
+pub mod cache;
+pub mod cancel;
+pub mod encode;
+pub mod error;
+pub mod fake;
+pub mod gemini;
+pub mod http;
 pub mod image;
+pub mod journal;
+pub mod ledger;
+pub mod metadata;
+pub mod ocr;
+pub mod pack;
+pub mod plan;
+pub mod profile;
+pub mod prompt;
+pub mod provider;
+pub mod recorder;
+pub mod retry;
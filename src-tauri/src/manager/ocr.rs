@@ -0,0 +1,289 @@
+use std::{collections::HashMap, path::PathBuf};
+
+use async_trait::async_trait;
+use log::info;
+use tokio::process::Command;
+
+use crate::manager::{
+    error::ProviderError,
+    provider::{NamingProvider, Suggestion},
+};
+
+// launchd starts the daemon with a bare PATH, so look in the homebrew
+// prefixes before falling back to whatever is on PATH
+const TESSERACT_PATHS: [&str; 3] = [
+    "/opt/homebrew/bin/tesseract",
+    "/usr/local/bin/tesseract",
+    "tesseract",
+];
+
+const MAX_WORDS: usize = 6;
+const MIN_CONFIDENCE: f64 = 60.0;
+
+const STOPWORDS: [&str; 24] = [
+    "the", "and", "for", "with", "from", "this", "that", "are", "was", "you", "your", "have",
+    "has", "not", "but", "all", "can", "will", "into", "our", "its", "there", "their", "about",
+];
+
+#[derive(Debug, Default)]
+struct Line {
+    top: u32,
+    heights: Vec<u32>,
+    words: Vec<String>,
+}
+
+impl Line {
+    fn average_height(&self) -> f64 {
+        if self.heights.is_empty() {
+            return 0.0;
+        }
+        self.heights.iter().sum::<u32>() as f64 / self.heights.len() as f64
+    }
+}
+
+// Fully local namer: runs tesseract over the image and builds a filename
+// from the most prominent text, so the daemon keeps working offline.
+#[derive(Debug, Clone, Default)]
+pub struct Ocr;
+
+impl Ocr {
+    pub fn new() -> Self {
+        Self
+    }
+
+    async fn run_tesseract(&self, image_path: &PathBuf) -> Result<String, ProviderError> {
+        for binary in TESSERACT_PATHS {
+            let output = match Command::new(binary)
+                .arg(image_path)
+                .arg("stdout")
+                .arg("tsv")
+                .output()
+                .await
+            {
+                Ok(output) => output,
+                Err(_) => continue,
+            };
+
+            if !output.status.success() {
+                return Err(ProviderError::MalformedResponse(format!(
+                    "tesseract failed on {:?}: {}",
+                    image_path,
+                    String::from_utf8_lossy(&output.stderr).trim()
+                )));
+            }
+            return Ok(String::from_utf8_lossy(&output.stdout).to_string());
+        }
+
+        Err(ProviderError::Unavailable(
+            "tesseract not found, install it with `brew install tesseract`".to_string(),
+        ))
+    }
+
+    // picks keywords from the tsv output, biggest text first. Lines in the top
+    // band of the image (window titles, tab bars) get a boost.
+    fn keywords(&self, tsv: &str) -> Vec<String> {
+        let mut page_height = 0;
+        let mut lines: HashMap<(u32, u32, u32), Line> = HashMap::new();
+
+        // level page block par line word left top width height conf text
+        for row in tsv.lines().skip(1) {
+            let columns: Vec<&str> = row.split('\t').collect();
+            if columns.len() < 12 {
+                continue;
+            }
+
+            let number = |index: usize| columns[index].parse::<u32>().unwrap_or(0);
+            match number(0) {
+                1 => page_height = number(9),
+                5 => {
+                    let confidence = columns[10].parse::<f64>().unwrap_or(0.0);
+                    let word: String = columns[11]
+                        .chars()
+                        .filter(|c| c.is_ascii_alphanumeric())
+                        .collect::<String>()
+                        .to_lowercase();
+                    if confidence < MIN_CONFIDENCE
+                        || word.len() < 3
+                        || STOPWORDS.contains(&word.as_str())
+                    {
+                        continue;
+                    }
+
+                    let line = lines.entry((number(2), number(3), number(4))).or_default();
+                    if line.words.is_empty() {
+                        line.top = number(7);
+                    }
+                    line.heights.push(number(9));
+                    line.words.push(word);
+                }
+                _ => {}
+            }
+        }
+
+        let score = |line: &Line| {
+            let in_title_band = page_height > 0 && line.top < page_height / 10;
+            line.average_height() * if in_title_band { 1.5 } else { 1.0 }
+        };
+
+        let mut lines: Vec<Line> = lines.into_values().collect();
+        lines.sort_by(|a, b| score(b).total_cmp(&score(a)));
+
+        let mut keywords: Vec<String> = vec![];
+        for word in lines.into_iter().flat_map(|line| line.words) {
+            if keywords.len() == MAX_WORDS {
+                break;
+            }
+            if !keywords.contains(&word) {
+                keywords.push(word);
+            }
+        }
+        keywords
+    }
+
+    // names the image after its keywords, None when no text was readable
+    fn suggestion(&self, tsv: &str) -> Option<Suggestion> {
+        let keywords = self.keywords(tsv);
+        if keywords.is_empty() {
+            return None;
+        }
+
+        Some(Suggestion {
+            name: keywords.join("-"),
+            tags: keywords,
+            finish_reason: Some("stop".to_string()),
+            ..Default::default()
+        })
+    }
+}
+
+#[async_trait]
+impl NamingProvider for Ocr {
+    fn id(&self) -> &'static str {
+        "ocr"
+    }
+
+    async fn probe(&self) -> Result<(), anyhow::Error> {
+        for binary in TESSERACT_PATHS {
+            if Command::new(binary).arg("--version").output().await.is_ok() {
+                return Ok(());
+            }
+        }
+        Err(ProviderError::Unavailable(
+            "tesseract not found, install it with `brew install tesseract`".to_string(),
+        )
+        .into())
+    }
+
+    async fn suggest_name(&self, image_path: &PathBuf) -> Result<Suggestion, ProviderError> {
+        info!("Getting local name for image: {:?}", image_path.display());
+        let tsv = self.run_tesseract(image_path).await?;
+        self.suggestion(&tsv).ok_or_else(|| {
+            ProviderError::MalformedResponse(format!(
+                "No readable text found in image: {:?}",
+                image_path
+            ))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEADER: &str =
+        "level\tpage_num\tblock_num\tpar_num\tline_num\tword_num\tleft\ttop\twidth\theight\tconf\ttext";
+
+    fn page(height: u32) -> String {
+        format!("1\t1\t0\t0\t0\t0\t0\t0\t1000\t{}\t-1\t", height)
+    }
+
+    fn word(line: u32, top: u32, height: u32, confidence: f64, text: &str) -> String {
+        format!(
+            "5\t1\t1\t1\t{}\t1\t0\t{}\t100\t{}\t{}\t{}",
+            line, top, height, confidence, text
+        )
+    }
+
+    fn tsv(rows: &[String]) -> String {
+        let mut lines = vec![HEADER.to_string()];
+        lines.extend(rows.iter().cloned());
+        lines.join("\n")
+    }
+
+    #[test]
+    fn drops_noise_words() {
+        let tsv = tsv(&[
+            page(1000),
+            word(1, 500, 20, 95.0, "The"),
+            word(1, 500, 20, 95.0, "Invoice"),
+            word(1, 500, 20, 95.0, "to"),
+            word(1, 500, 20, 30.0, "blurry"),
+            word(1, 500, 20, 95.0, "#2024!"),
+        ]);
+        assert_eq!(Ocr::new().keywords(&tsv), vec!["invoice", "2024"]);
+    }
+
+    #[test]
+    fn bigger_text_comes_first() {
+        let tsv = tsv(&[
+            page(1000),
+            word(1, 600, 12, 90.0, "footer"),
+            word(2, 400, 40, 90.0, "headline"),
+        ]);
+        assert_eq!(Ocr::new().keywords(&tsv), vec!["headline", "footer"]);
+    }
+
+    #[test]
+    fn title_band_is_boosted() {
+        // 20px in the top tenth scores 30, beating 25px further down
+        let tsv = tsv(&[
+            page(1000),
+            word(1, 500, 25, 90.0, "body"),
+            word(2, 10, 20, 90.0, "window"),
+        ]);
+        assert_eq!(Ocr::new().keywords(&tsv), vec!["window", "body"]);
+    }
+
+    #[test]
+    fn keywords_are_unique_and_capped() {
+        let words = [
+            "alpha", "bravo", "alpha", "charlie", "delta", "echo", "foxtrot", "golf",
+        ];
+        let mut rows = vec![page(1000)];
+        rows.extend(words.iter().map(|text| word(1, 500, 20, 90.0, text)));
+        assert_eq!(
+            Ocr::new().keywords(&tsv(&rows)),
+            vec!["alpha", "bravo", "charlie", "delta", "echo", "foxtrot"]
+        );
+    }
+
+    #[test]
+    fn skips_malformed_rows() {
+        let tsv = tsv(&[
+            page(1000),
+            "5\t1\t1".to_string(),
+            word(1, 500, 20, 90.0, "settings"),
+        ]);
+        assert_eq!(Ocr::new().keywords(&tsv), vec!["settings"]);
+    }
+
+    #[test]
+    fn names_after_keywords() {
+        let tsv = tsv(&[
+            page(1000),
+            word(1, 10, 30, 90.0, "Pull"),
+            word(1, 10, 30, 90.0, "Requests"),
+            word(2, 500, 14, 90.0, "github"),
+        ]);
+        let suggestion = Ocr::new().suggestion(&tsv).unwrap();
+        assert_eq!(suggestion.name, "pull-requests-github");
+        assert_eq!(suggestion.tags, vec!["pull", "requests", "github"]);
+    }
+
+    #[test]
+    fn no_text_means_no_name() {
+        let tsv = tsv(&[page(1000), word(1, 500, 20, 20.0, "smudge")]);
+        assert!(Ocr::new().suggestion(&tsv).is_none());
+        assert!(Ocr::new().suggestion("").is_none());
+    }
+}
@@ -0,0 +1,105 @@
+use std::{path::PathBuf, sync::Arc};
+
+use log::error;
+
+use crate::{
+    config::config::Config,
+    manager::{
+        ai::OpenAI,
+        cache::ResponseCache,
+        error::ProviderError,
+        ledger::{Ledger, SpendingCaps},
+        provider::{self, NamingProvider, Suggestion},
+    },
+};
+
+// Sends several images of a bulk run in one OpenAI request to save the
+// per-call overhead. Goes around the provider stack, so it does its own
+// caching and metering; answers are not recorded. `Config::check_packing`
+// keeps it to profiles where nothing else in the stack would change a name.
+#[derive(Debug)]
+pub struct Packer {
+    openai: OpenAI,
+    size: usize,
+    ledger: Ledger,
+    caps: SpendingCaps,
+    cache: Option<ResponseCache>,
+}
+
+impl Packer {
+    // None when packing is off, an error when the profile can't be packed
+    pub fn from_config(config: &Config) -> Result<Option<Self>, anyhow::Error> {
+        let size = config.get_images_per_request() as usize;
+        if size < 2 {
+            return Ok(None);
+        }
+        config.check_packing()?;
+
+        let openai = provider::build_openai(config)?;
+        let cache = config
+            .cache_enabled()
+            .then(|| ResponseCache::new(Arc::new(openai.clone())));
+        Ok(Some(Self {
+            openai,
+            size,
+            ledger: Ledger::new(config),
+            caps: SpendingCaps::from_config(config),
+            cache,
+        }))
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    // cached images are answered from the cache, only the rest is sent
+    pub async fn suggest_names(
+        &self,
+        image_paths: &[PathBuf],
+    ) -> Result<Vec<Suggestion>, ProviderError> {
+        let fingerprints: Vec<String> = image_paths
+            .iter()
+            .map(|path| self.openai.packed_fingerprint(path))
+            .collect();
+        let mut suggestions: Vec<Option<Suggestion>> = image_paths
+            .iter()
+            .zip(fingerprints.iter())
+            .map(|(path, fingerprint)| {
+                self.cache
+                    .as_ref()
+                    .and_then(|cache| cache.lookup(path, fingerprint))
+            })
+            .collect();
+
+        let misses: Vec<usize> = (0..image_paths.len())
+            .filter(|index| suggestions[*index].is_none())
+            .collect();
+        if !misses.is_empty() {
+            self.caps.check(&self.ledger)?;
+
+            let paths: Vec<PathBuf> = misses
+                .iter()
+                .map(|index| image_paths[*index].clone())
+                .collect();
+            let answers = self.openai.suggest_names(&paths).await?;
+            for (index, suggestion) in misses.into_iter().zip(answers) {
+                let path = &image_paths[index];
+                if let Some(usage) = suggestion.usage.as_ref() {
+                    if let Err(e) =
+                        self.ledger
+                            .record(self.openai.id(), self.openai.model(), usage, path)
+                    {
+                        error!("Failed to record usage for {:?}: {}", path, e);
+                    }
+                }
+                if let Some(cache) = self.cache.as_ref() {
+                    cache.store(path, &fingerprints[index], &suggestion);
+                }
+                suggestions[index] = Some(suggestion);
+            }
+        }
+
+        // every miss got an answer, parse_packed makes sure of that
+        Ok(suggestions.into_iter().flatten().collect())
+    }
+}
@@ -0,0 +1,147 @@
+use std::{fs, path::Path};
+
+use log::error;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    manager::{
+        image::Renamed,
+        journal::{self, Journal, Origin},
+        metadata,
+        provider::Suggestion,
+    },
+    utils::{mover, time},
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlanEntry {
+    pub from: String,
+    pub to: String,
+    // sha256 at planning time, apply skips files that changed since
+    pub hash: String,
+    pub profile: String,
+    pub provider: String,
+    pub model: String,
+    // from structured output, embedded on apply like on a direct rename
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+// Renames worked out by a dry run, to be reviewed and possibly edited
+// before `peeksy apply` carries them out. `to` can be changed freely, it is
+// used as is.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Plan {
+    pub created_at: u64,
+    pub entries: Vec<PlanEntry>,
+}
+
+impl Plan {
+    pub fn new() -> Self {
+        Self {
+            created_at: time::unix_now(),
+            entries: vec![],
+        }
+    }
+
+    pub fn push(&mut self, renamed: &Renamed, origin: &Origin) -> Result<(), anyhow::Error> {
+        // the plan may be applied from another directory
+        let absolute =
+            |path: &Path| std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+        self.entries.push(PlanEntry {
+            from: absolute(&renamed.from).display().to_string(),
+            to: absolute(&renamed.to).display().to_string(),
+            hash: journal::hash_file(&renamed.from)?,
+            profile: origin.profile.clone(),
+            provider: origin.provider.clone(),
+            model: origin.model.clone(),
+            description: renamed.suggestion.description.clone(),
+            tags: renamed.suggestion.tags.clone(),
+        });
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> Result<Self, anyhow::Error> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("Failed to read plan: {:?}, Error: {}", path, e))?;
+        serde_json::from_str(&content)
+            .map_err(|e| anyhow::anyhow!("Failed to parse plan: {:?}, Error: {}", path, e))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), anyhow::Error> {
+        fs::write(path, serde_json::to_string_pretty(self)?)
+            .map_err(|e| anyhow::anyhow!("Failed to write plan: {:?}, Error: {}", path, e))
+    }
+
+    // old -> new, one rename per line
+    pub fn print(&self) {
+        let width = self
+            .entries
+            .iter()
+            .map(|entry| file_name(&entry.from).len())
+            .max()
+            .unwrap_or(0);
+        for entry in self.entries.iter() {
+            println!(
+                "{:<width$}  ->  {}",
+                file_name(&entry.from),
+                file_name(&entry.to),
+                width = width
+            );
+        }
+        println!("{} renames planned", self.entries.len());
+    }
+}
+
+fn file_name(path: &str) -> String {
+    Path::new(path)
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.to_string())
+}
+
+// Carries out one planned rename without asking the provider. Refuses when
+// the file is gone or changed since planning or the target got taken, the
+// rename is journaled as part of `run`.
+pub fn apply(
+    entry: &PlanEntry,
+    journal: &Journal,
+    run: &str,
+    embed_metadata: bool,
+) -> Result<(), anyhow::Error> {
+    let (from, to) = (Path::new(&entry.from), Path::new(&entry.to));
+    if !from.exists() {
+        return Err(anyhow::anyhow!("{:?} no longer exists", from));
+    }
+    if journal::hash_file(from)? != entry.hash {
+        return Err(anyhow::anyhow!("{:?} has changed since planning", from));
+    }
+
+    mover::move_file(from, to).map_err(|e| match mover::is_taken(&e) {
+        true => anyhow::anyhow!("{:?} is taken by another file", to),
+        false => e,
+    })?;
+
+    // before journaling, so the journaled hash is that of the final file
+    if embed_metadata {
+        let suggestion = Suggestion {
+            description: entry.description.clone(),
+            tags: entry.tags.clone(),
+            ..Default::default()
+        };
+        if let Err(e) = metadata::embed(to, &suggestion) {
+            error!("Failed to embed metadata into {:?}: {}", to, e);
+        }
+    }
+
+    let origin = Origin {
+        provider: entry.provider.clone(),
+        model: entry.model.clone(),
+        profile: entry.profile.clone(),
+        run: Some(run.to_string()),
+    };
+    journal.record(from, to, &origin, None)?;
+    Ok(())
+}
@@ -0,0 +1,330 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use async_trait::async_trait;
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::config::Config,
+    manager::{
+        error::ProviderError,
+        provider::{NamingProvider, Suggestion},
+    },
+};
+
+pub const DEFAULT_PROFILE: &str = "default";
+
+// A named bundle of prompt, provider, model and parameters. Anything left
+// out falls back to the top level config.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Profile {
+    pub prompt_file_path: Option<String>,
+    pub provider: Option<String>,
+    pub model: Option<String>,
+    pub structured_output: Option<bool>,
+    pub max_image_dimension: Option<u32>,
+    pub image_detail: Option<String>,
+    // kebab (screenshot-of-login-page) or snake (screenshot_of_login_page)
+    pub naming_style: Option<String>,
+}
+
+// Picks a profile for files in `directory`, with `extension` or whose name
+// matches `pattern` (* and ? wildcards). Every condition that is set has to
+// match, the first matching rule wins.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ProfileRule {
+    pub profile: String,
+    pub directory: Option<String>,
+    pub extension: Option<String>,
+    pub pattern: Option<String>,
+}
+
+impl Profile {
+    // the top level config with this profile laid over it
+    pub fn apply(&self, config: &Config) -> Config {
+        let mut config = config.clone();
+        if self.prompt_file_path.is_some() {
+            config.openai_prompt_file_path = self.prompt_file_path.clone();
+        }
+        if self.provider.is_some() {
+            config.provider = self.provider.clone();
+        }
+        if self.model.is_some() {
+            match config.get_provider().as_str() {
+                "anthropic" => config.anthropic_model = self.model.clone(),
+                "gemini" => config.gemini_model = self.model.clone(),
+                _ => config.openai_model = self.model.clone(),
+            }
+        }
+        if self.structured_output.is_some() {
+            config.structured_output = self.structured_output;
+        }
+        if self.max_image_dimension.is_some() {
+            config.max_image_dimension = self.max_image_dimension;
+        }
+        if self.image_detail.is_some() {
+            config.image_detail = self.image_detail.clone();
+        }
+        if self.naming_style.is_some() {
+            config.naming_style = self.naming_style.clone();
+        }
+        config
+    }
+}
+
+impl ProfileRule {
+    pub fn matches(&self, path: &Path) -> bool {
+        if let Some(directory) = self.directory.as_ref() {
+            let directory = expand_home(directory);
+            if !path
+                .parent()
+                .is_some_and(|parent| parent.starts_with(&directory))
+            {
+                return false;
+            }
+        }
+
+        if let Some(extension) = self.extension.as_ref() {
+            let extension = extension.trim_start_matches('.');
+            if !path
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .is_some_and(|ext| ext.eq_ignore_ascii_case(extension))
+            {
+                return false;
+            }
+        }
+
+        if let Some(pattern) = self.pattern.as_ref() {
+            let name = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .unwrap_or_default()
+                .trim_start_matches('.')
+                .to_lowercase();
+            if !wildcard_match(&pattern.to_lowercase(), &name) {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+fn expand_home(directory: &str) -> PathBuf {
+    match (directory.strip_prefix("~/"), dirs::home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(directory),
+    }
+}
+
+// glob style matching on a single file name, * is any run of characters and
+// ? any single character
+fn wildcard_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, n));
+            p += 1;
+        } else if let Some((star, matched)) = backtrack {
+            p = star + 1;
+            n = matched + 1;
+            backtrack = Some((star, matched + 1));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+// the profile a file gets: the first matching rule, else `default_profile`,
+// else the plain top level config
+pub fn select(config: &Config, path: &Path) -> String {
+    config
+        .profile_rules
+        .iter()
+        .flatten()
+        .find(|rule| rule.matches(path))
+        .map(|rule| rule.profile.clone())
+        .or_else(|| config.default_profile.clone())
+        .unwrap_or_else(|| DEFAULT_PROFILE.to_string())
+}
+
+// files split up by the profile they get, in order of first appearance
+pub fn group(config: &Config, files: Vec<PathBuf>) -> Vec<(String, Vec<PathBuf>)> {
+    let mut groups: Vec<(String, Vec<PathBuf>)> = vec![];
+    for file in files {
+        let name = select(config, &file);
+        match groups.iter_mut().find(|(profile, _)| *profile == name) {
+            Some((_, files)) => files.push(file),
+            None => groups.push((name, vec![file])),
+        }
+    }
+    groups
+}
+
+// the top level config for `default`, otherwise the named profile laid over it
+pub fn resolve(config: &Config, name: &str) -> Result<Config, anyhow::Error> {
+    if name == DEFAULT_PROFILE {
+        return Ok(config.clone());
+    }
+
+    match config.profiles.as_ref().and_then(|p| p.get(name)) {
+        Some(profile) => Ok(profile.apply(config)),
+        None => Err(anyhow::anyhow!("Unknown profile: {}", name)),
+    }
+}
+
+// Routes every image to the provider of the profile its rules select and
+// carries that profile's naming style. Always the outermost provider.
+#[derive(Debug)]
+pub struct Profiled {
+    config: Config,
+    profiles: Vec<(String, Arc<dyn NamingProvider>, String)>,
+}
+
+impl Profiled {
+    // builds one provider stack per profile that can be selected
+    pub fn new<F>(config: &Config, mut build: F) -> Result<Self, anyhow::Error>
+    where
+        F: FnMut(&Config) -> Result<Arc<dyn NamingProvider>, anyhow::Error>,
+    {
+        let mut names = vec![config
+            .default_profile
+            .clone()
+            .unwrap_or_else(|| DEFAULT_PROFILE.to_string())];
+        for rule in config.profile_rules.iter().flatten() {
+            if !names.contains(&rule.profile) {
+                names.push(rule.profile.clone());
+            }
+        }
+
+        // a broken rule profile is left out, the files it selects fall back
+        // to the default profile. Only the default one has to work.
+        let mut profiles = vec![];
+        for (i, name) in names.into_iter().enumerate() {
+            let built = resolve(config, &name)
+                .and_then(|profile_config| Ok((build(&profile_config)?, profile_config)));
+            match built {
+                Ok((provider, profile_config)) => {
+                    profiles.push((name, provider, profile_config.get_naming_style()))
+                }
+                Err(e) if i == 0 => {
+                    return Err(anyhow::anyhow!("Failed to setup profile {}: {}", name, e))
+                }
+                Err(e) => error!(
+                    "Skipping profile {}, its files use the default profile. Error: {}",
+                    name, e
+                ),
+            }
+        }
+
+        Ok(Self {
+            config: config.clone(),
+            profiles,
+        })
+    }
+
+    fn profile(&self, image_path: &PathBuf) -> &(String, Arc<dyn NamingProvider>, String) {
+        let name = select(&self.config, image_path);
+        self.profiles
+            .iter()
+            .find(|(profile, _, _)| *profile == name)
+            .unwrap_or(&self.profiles[0])
+    }
+
+    fn default(&self) -> &Arc<dyn NamingProvider> {
+        &self.profiles[0].1
+    }
+}
+
+#[async_trait]
+impl NamingProvider for Profiled {
+    fn id(&self) -> &'static str {
+        self.default().id()
+    }
+
+    fn model(&self) -> &str {
+        self.default().model()
+    }
+
+    fn fingerprint(&self, image_path: &PathBuf) -> String {
+        self.profile(image_path).1.fingerprint(image_path)
+    }
+
+    fn naming_style(&self, image_path: &PathBuf) -> &str {
+        &self.profile(image_path).2
+    }
+
+    fn source(&self, image_path: &PathBuf) -> (&str, &str, &str) {
+        let (name, provider, _) = self.profile(image_path);
+        (name, provider.id(), provider.model())
+    }
+
+    async fn probe(&self) -> Result<(), anyhow::Error> {
+        for (_, provider, _) in self.profiles.iter() {
+            provider.probe().await?;
+        }
+        Ok(())
+    }
+
+    async fn suggest_name(&self, image_path: &PathBuf) -> Result<Suggestion, ProviderError> {
+        let (name, provider, _) = self.profile(image_path);
+        if self.profiles.len() > 1 {
+            info!("Using profile {} for {:?}", name, image_path.display());
+        }
+        provider.suggest_name(image_path).await
+    }
+
+    async fn suggest_distinct_name(
+        &self,
+        image_path: &PathBuf,
+        taken: &[String],
+    ) -> Result<Suggestion, ProviderError> {
+        self.profile(image_path)
+            .1
+            .suggest_distinct_name(image_path, taken)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manager::fake::Fake;
+
+    fn build(config: &Config) -> Result<Arc<dyn NamingProvider>, anyhow::Error> {
+        Ok(Arc::new(Fake::from_config(config)?))
+    }
+
+    #[test]
+    fn skips_broken_profiles() {
+        let config: Config = serde_json::from_str(
+            r#"{"provider": "fake", "profile_rules": [{"profile": "missing", "extension": "png"}]}"#,
+        )
+        .unwrap();
+        let profiled = Profiled::new(&config, build).unwrap();
+        assert_eq!(profiled.profiles.len(), 1);
+
+        let image = PathBuf::from("shot.png");
+        assert_eq!(profiled.source(&image).0, DEFAULT_PROFILE);
+    }
+
+    #[test]
+    fn fails_without_default_profile() {
+        let config: Config =
+            serde_json::from_str(r#"{"provider": "fake", "default_profile": "missing"}"#).unwrap();
+        assert!(Profiled::new(&config, build).is_err());
+    }
+}
@@ -0,0 +1,166 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use log::warn;
+
+use crate::utils::time;
+
+// keeps the prompt short in folders with thousands of files
+const MAX_NEIGHBORS: usize = 20;
+
+// the prompt with a note that these names are already in use
+pub fn distinct(prompt: &str, taken: &[String]) -> String {
+    format!(
+        "{}\n\nThese names are already used by other files: {}. Answer with a different, more specific name.",
+        prompt,
+        taken.join(", ")
+    )
+}
+
+// Renders `{{variable}}` placeholders in the prompt file with details of the
+// file being named:
+//
+// {{original_name}}   file name without extension
+// {{extension}}       extension without the dot
+// {{parent_dir}}      name of the folder the file lives in
+// {{created_at}}      creation time, 2025-01-31 14:25:01 utc
+// {{neighbor_names}}  names of the most recent files in the same folder
+// {{exif.<key>}}      image metadata as reported by sips, e.g. {{exif.make}}
+//
+// Unknown placeholders are left as they are, missing exif keys render empty.
+pub fn render(template: &str, image_path: &PathBuf) -> String {
+    if !template.contains("{{") {
+        return template.to_string();
+    }
+
+    let mut exif: Option<HashMap<String, String>> = None;
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find("{{") {
+        let end = match rest[start..].find("}}") {
+            Some(end) => start + end,
+            None => break,
+        };
+
+        rendered.push_str(&rest[..start]);
+        let variable = rest[start + 2..end].trim();
+        let value = match variable {
+            "original_name" => original_name(image_path),
+            "extension" => extension(image_path),
+            "parent_dir" => parent_dir(image_path),
+            "created_at" => created_at(image_path),
+            "neighbor_names" => neighbor_names(image_path),
+            _ => match variable.strip_prefix("exif.") {
+                Some(key) => exif
+                    .get_or_insert_with(|| read_exif(image_path))
+                    .get(&key.to_lowercase())
+                    .cloned()
+                    .unwrap_or_default(),
+                None => {
+                    warn!("Unknown prompt variable: {}", variable);
+                    rest[start..end + 2].to_string()
+                }
+            },
+        };
+        rendered.push_str(&value);
+        rest = &rest[end + 2..];
+    }
+
+    rendered.push_str(rest);
+    rendered
+}
+
+// the daemon sees screenshots while they are still hidden (.Screenshot ...)
+fn original_name(path: &Path) -> String {
+    path.file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or_default()
+        .trim_start_matches('.')
+        .to_string()
+}
+
+fn extension(path: &Path) -> String {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or_default()
+        .to_lowercase()
+}
+
+fn parent_dir(path: &Path) -> String {
+    path.parent()
+        .and_then(|parent| parent.file_name())
+        .and_then(|name| name.to_str())
+        .unwrap_or_default()
+        .to_string()
+}
+
+fn created_at(path: &Path) -> String {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.created().or_else(|_| metadata.modified()))
+        .ok()
+        .and_then(|created| created.duration_since(UNIX_EPOCH).ok())
+        .map(|created| time::datetime(created.as_secs()))
+        .unwrap_or_default()
+}
+
+// visible files next to this one, newest first, so the model can follow the
+// naming already used in the folder
+fn neighbor_names(path: &Path) -> String {
+    let parent = match path.parent() {
+        Some(parent) => parent,
+        None => return String::new(),
+    };
+    let entries = match fs::read_dir(parent) {
+        Ok(entries) => entries,
+        Err(_) => return String::new(),
+    };
+
+    let mut neighbors: Vec<(SystemTime, String)> = entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path() != path)
+        .filter_map(|entry| {
+            let name = entry.file_name().to_str()?.to_string();
+            if name.starts_with('.') {
+                return None;
+            }
+            let modified = entry.metadata().and_then(|m| m.modified()).ok()?;
+            Some((modified, name))
+        })
+        .collect();
+
+    neighbors.sort_by_key(|neighbor| std::cmp::Reverse(neighbor.0));
+    neighbors
+        .into_iter()
+        .take(MAX_NEIGHBORS)
+        .map(|(_, name)| name)
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+// `sips -g all` lists the image properties (make, model, creation, ...) as
+// `key: value` lines below the file path
+fn read_exif(path: &Path) -> HashMap<String, String> {
+    let output = match Command::new("sips").args(["-g", "all"]).arg(path).output() {
+        Ok(output) if output.status.success() => output,
+        _ => {
+            warn!("Failed to read image properties for {:?}", path);
+            return HashMap::new();
+        }
+    };
+
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let (key, value) = line.trim().split_once(':')?;
+            Some((key.trim().to_lowercase(), value.trim().to_string()))
+        })
+        .filter(|(_, value)| value != "<nil>")
+        .collect()
+}
@@ -0,0 +1,488 @@
+use std::{fmt::Debug, path::PathBuf, sync::Arc};
+
+use async_trait::async_trait;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::{
+    config::config::Config,
+    manager::{
+        ai::{OpenAI, DEFAULT_BASE_URL},
+        anthropic::Anthropic,
+        cache::ResponseCache,
+        encode::ImageOptions,
+        error::ProviderError,
+        fake::Fake,
+        gemini::Gemini,
+        http,
+        ledger::Metered,
+        ocr::Ocr,
+        profile::{Profiled, DEFAULT_PROFILE},
+        recorder::{RecordMode, Recorder},
+        retry::RetryPolicy,
+    },
+};
+
+pub const SYSTEM_PROMPT: &str = r#"You are a filename generation bot. You must return only a filename based on the attached image. No explanations.
+                         No descriptions. No punctuation. No quotes. No code blocks. Just a lowercase hyphenated filename of 3 to 8 words in plain text."#;
+
+pub const STRUCTURED_SYSTEM_PROMPT: &str = r#"You are a filename generation bot. Look at the attached image and answer with a single JSON object and nothing else.
+                         Fields: "filename" a lowercase hyphenated filename of 3 to 8 words without extension, "description" one sentence describing the image,
+                         "tags" up to 8 lowercase keywords, "category" one word such as screenshot, photo, document, diagram, artwork or meme,
+                         "confidence" a number between 0 and 1 for how sure you are about the filename."#;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Usage {
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Suggestion {
+    pub name: String,
+    // only filled in structured output mode
+    pub description: Option<String>,
+    pub tags: Vec<String>,
+    pub category: Option<String>,
+    pub confidence: Option<f64>,
+    // other names to pick from, only filled when more than one candidate
+    // was asked for
+    #[serde(default)]
+    pub alternatives: Vec<String>,
+    // normalised to the openai vocabulary: stop, length, content_filter
+    pub finish_reason: Option<String>,
+    pub usage: Option<Usage>,
+}
+
+impl Suggestion {
+    pub fn new(name: String) -> Self {
+        Self {
+            name,
+            ..Default::default()
+        }
+    }
+
+    // builds a suggestion from the model's answer, either a bare filename or
+    // the json object asked for in structured output mode
+    pub fn parse(content: &str, structured: bool) -> Result<Self, ProviderError> {
+        if !structured {
+            return Ok(Self::new(content.trim().to_string()));
+        }
+
+        let content = content
+            .trim()
+            .trim_start_matches("```json")
+            .trim_matches('`')
+            .trim();
+        let value: serde_json::Value = serde_json::from_str(content)?;
+        let name = value["filename"].as_str().ok_or_else(|| {
+            ProviderError::MalformedResponse(format!("no filename in response: {}", content))
+        })?;
+
+        Ok(Self {
+            name: name.to_string(),
+            description: value["description"].as_str().map(|d| d.to_string()),
+            tags: value["tags"]
+                .as_array()
+                .map(|tags| {
+                    tags.iter()
+                        .filter_map(|tag| tag.as_str().map(|tag| tag.to_string()))
+                        .collect()
+                })
+                .unwrap_or_default(),
+            category: value["category"].as_str().map(|c| c.to_string()),
+            confidence: value["confidence"].as_f64(),
+            alternatives: value["alternatives"]
+                .as_array()
+                .map(|names| {
+                    names
+                        .iter()
+                        .filter_map(|name| name.as_str().map(|name| name.to_string()))
+                        .collect()
+                })
+                .unwrap_or_default(),
+            ..Default::default()
+        })
+    }
+
+    // for providers without an `n` parameter the model is asked for several
+    // names at once, one per line in plain mode or an "alternatives" array in
+    // structured mode
+    pub fn parse_candidates(content: &str, structured: bool) -> Result<Self, ProviderError> {
+        let mut suggestion = Self::parse(content, structured)?;
+        if !structured {
+            let mut lines = content
+                .lines()
+                .map(|line| line.trim())
+                .filter(|line| !line.is_empty() && !line.starts_with("```"));
+            suggestion.name = lines.next().unwrap_or_default().to_string();
+            suggestion.alternatives = lines.map(|line| line.to_string()).collect();
+        }
+        Ok(suggestion)
+    }
+
+    // folds the choices of one response into the first, the others become
+    // alternatives
+    pub fn merge(choices: Vec<Suggestion>) -> Option<Self> {
+        let mut choices = choices.into_iter();
+        let mut suggestion = choices.next()?;
+        suggestion
+            .alternatives
+            .extend(choices.map(|choice| choice.name));
+        Some(suggestion)
+    }
+
+    // the name followed by the alternatives, without duplicates
+    pub fn candidates(&self) -> Vec<String> {
+        let mut candidates: Vec<String> = vec![];
+        for name in std::iter::once(&self.name).chain(self.alternatives.iter()) {
+            if !name.is_empty() && !candidates.contains(name) {
+                candidates.push(name.clone());
+            }
+        }
+        candidates
+    }
+}
+
+// json schema for structured output mode, shared by every provider that
+// supports constrained decoding
+pub fn suggestion_schema() -> serde_json::Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "filename": { "type": "string" },
+            "description": { "type": "string" },
+            "tags": { "type": "array", "items": { "type": "string" } },
+            "category": { "type": "string" },
+            "confidence": { "type": "number" }
+        },
+        "required": ["filename", "description", "tags", "category", "confidence"],
+        "additionalProperties": false
+    })
+}
+
+pub fn system_prompt(structured: bool) -> &'static str {
+    if structured {
+        STRUCTURED_SYSTEM_PROMPT
+    } else {
+        SYSTEM_PROMPT
+    }
+}
+
+// Anything that can look at an image and come up with a name for it.
+// SSManager, the daemon and the cli only talk to this trait, the concrete
+// backend is picked from the config by `from_config`.
+#[async_trait]
+pub trait NamingProvider: Debug + Send + Sync {
+    fn id(&self) -> &'static str;
+
+    fn model(&self) -> &str {
+        ""
+    }
+
+    // identifies everything that shapes the answer for this image (provider,
+    // endpoint, model, rendered prompt), used to key the response cache
+    fn fingerprint(&self, _image_path: &PathBuf) -> String {
+        self.id().to_string()
+    }
+
+    // word separator for names of this image, see `filename::styled`
+    fn naming_style(&self, _image_path: &PathBuf) -> &str {
+        "kebab"
+    }
+
+    // profile, provider and model that name this image, for the journal
+    fn source(&self, _image_path: &PathBuf) -> (&str, &str, &str) {
+        (DEFAULT_PROFILE, self.id(), self.model())
+    }
+
+    // checks that the backend is reachable and can handle images
+    async fn probe(&self) -> Result<(), anyhow::Error> {
+        Ok(())
+    }
+
+    async fn suggest_name(&self, image_path: &PathBuf) -> Result<Suggestion, ProviderError>;
+
+    // asks again when the suggested name is already used by another file,
+    // backends that can't be steered just answer as before
+    async fn suggest_distinct_name(
+        &self,
+        image_path: &PathBuf,
+        _taken: &[String],
+    ) -> Result<Suggestion, ProviderError> {
+        self.suggest_name(image_path).await
+    }
+}
+
+pub fn read_prompt(config: &Config) -> Result<String, anyhow::Error> {
+    let prompt_path = match config.get_openai_prompt_file_path() {
+        Some(path) => path,
+        None => return Err(anyhow::anyhow!("Prompt file path is not set")),
+    };
+
+    std::fs::read_to_string(&prompt_path).map_err(|e| {
+        anyhow::anyhow!(
+            "Failed to read prompt file: {:?}, Error: {}",
+            prompt_path,
+            e
+        )
+    })
+}
+
+// Tries the configured provider first and names the image locally when it
+// fails, e.g. when the machine is offline. Sits below the retry policy of
+// SSManager, so it retries connection failures itself before giving up on the
+// provider.
+#[derive(Debug, Clone)]
+pub struct Fallback {
+    primary: Arc<dyn NamingProvider>,
+    fallback: Arc<dyn NamingProvider>,
+    retry: RetryPolicy,
+}
+
+impl Fallback {
+    pub fn new(
+        primary: Arc<dyn NamingProvider>,
+        fallback: Arc<dyn NamingProvider>,
+        retry: RetryPolicy,
+    ) -> Self {
+        Self {
+            primary,
+            fallback,
+            retry,
+        }
+    }
+}
+
+#[async_trait]
+impl NamingProvider for Fallback {
+    fn id(&self) -> &'static str {
+        self.primary.id()
+    }
+
+    fn model(&self) -> &str {
+        self.primary.model()
+    }
+
+    fn fingerprint(&self, image_path: &PathBuf) -> String {
+        self.primary.fingerprint(image_path)
+    }
+
+    async fn probe(&self) -> Result<(), anyhow::Error> {
+        self.primary.probe().await
+    }
+
+    async fn suggest_name(&self, image_path: &PathBuf) -> Result<Suggestion, ProviderError> {
+        // only connection failures are retried here, every other error comes
+        // out as the answer and rate limits and server errors are left to the
+        // outer retry policy
+        let result = self
+            .retry
+            .run(image_path, || async {
+                match self.primary.suggest_name(image_path).await {
+                    Err(e @ ProviderError::Network(_)) => Err(e),
+                    result => Ok(result),
+                }
+            })
+            .await
+            .and_then(|result| result);
+
+        match result {
+            // only a provider that stays unreachable means we are offline
+            Err(e @ ProviderError::Network(_)) | Err(e @ ProviderError::Unavailable(_)) => {
+                warn!(
+                    "{} failed, falling back to {}: {:?}",
+                    self.primary.id(),
+                    self.fallback.id(),
+                    e
+                );
+                self.fallback.suggest_name(image_path).await
+            }
+            result => result,
+        }
+    }
+
+    async fn suggest_distinct_name(
+        &self,
+        image_path: &PathBuf,
+        taken: &[String],
+    ) -> Result<Suggestion, ProviderError> {
+        self.primary.suggest_distinct_name(image_path, taken).await
+    }
+}
+
+// one provider stack per profile, routed by the profile rules
+pub fn from_config(config: &Config) -> Result<Arc<dyn NamingProvider>, anyhow::Error> {
+    http::init(config)?;
+    Ok(Arc::new(Profiled::new(config, build_stack)?))
+}
+
+fn build_stack(config: &Config) -> Result<Arc<dyn NamingProvider>, anyhow::Error> {
+    match config.get_provider().as_str() {
+        "ocr" => return Ok(Arc::new(Ocr::new())),
+        "fake" => return Ok(Arc::new(Fake::from_config(config)?)),
+        _ => {}
+    }
+
+    let primary = match build_provider(config).and_then(|provider| with_fixtures(provider, config))
+    {
+        // metered and cached inside the fallback so local ocr names never end
+        // up in the cache under the remote provider's key. Cache hits are free
+        // and skip the spending caps.
+        Ok(provider) if config.cache_enabled() => {
+            ResponseCache::wrap(Metered::wrap(provider, config))
+        }
+        Ok(provider) => Metered::wrap(provider, config),
+        Err(e) if config.offline_fallback_enabled() => {
+            warn!(
+                "Using local ocr namer, {} is not usable: {:?}",
+                config.get_provider(),
+                e
+            );
+            return Ok(Arc::new(Ocr::new()));
+        }
+        Err(e) => return Err(e),
+    };
+
+    if config.offline_fallback_enabled() {
+        return Ok(Arc::new(Fallback::new(
+            primary,
+            Arc::new(Ocr::new()),
+            RetryPolicy::from_config(config),
+        )));
+    }
+    Ok(primary)
+}
+
+// records or replays the raw provider answers, inside the meter so replays
+// cost nothing
+fn with_fixtures(
+    provider: Arc<dyn NamingProvider>,
+    config: &Config,
+) -> Result<Arc<dyn NamingProvider>, anyhow::Error> {
+    let mode = match config.fixture_mode.as_deref() {
+        Some(mode) if !mode.is_empty() => mode,
+        _ => return Ok(provider),
+    };
+
+    match RecordMode::parse(mode) {
+        Some(mode) => Recorder::wrap(provider, config.get_fixture_dir(), mode),
+        None => Err(anyhow::anyhow!(
+            "Unknown fixture mode: {}, use record or replay",
+            mode
+        )),
+    }
+}
+
+// the concrete openai provider, the batch api needs more than the trait
+pub fn build_openai(config: &Config) -> Result<OpenAI, anyhow::Error> {
+    let api_key = config.get_openai_api_key().filter(|key| !key.is_empty());
+    let model = config
+        .get_openai_model()
+        .ok_or_else(|| anyhow::anyhow!("OpenAI model is not set"))?;
+    let base_url = config.get_openai_base_url();
+    if api_key.is_none() && base_url == DEFAULT_BASE_URL {
+        return Err(anyhow::anyhow!("OpenAI API key is not set"));
+    }
+    Ok(OpenAI::new(
+        api_key,
+        read_prompt(config)?,
+        model,
+        base_url,
+        config.structured_output_enabled(),
+        ImageOptions::from_config(config),
+        config.get_name_candidates(),
+    ))
+}
+
+fn build_provider(config: &Config) -> Result<Arc<dyn NamingProvider>, anyhow::Error> {
+    let prompt = read_prompt(config)?;
+    let structured = config.structured_output_enabled();
+    let image = ImageOptions::from_config(config);
+    let candidates = config.get_name_candidates();
+
+    match config.get_provider().as_str() {
+        "openai" => Ok(Arc::new(build_openai(config)?)),
+        "anthropic" => {
+            let api_key = config
+                .get_anthropic_api_key()
+                .ok_or_else(|| anyhow::anyhow!("Anthropic API key is not set"))?;
+            let model = config
+                .get_anthropic_model()
+                .ok_or_else(|| anyhow::anyhow!("Anthropic model is not set"))?;
+            Ok(Arc::new(Anthropic::new(
+                api_key, prompt, model, structured, image, candidates,
+            )))
+        }
+        "gemini" => {
+            let api_key = config
+                .get_gemini_api_key()
+                .ok_or_else(|| anyhow::anyhow!("Gemini API key is not set"))?;
+            let model = config
+                .get_gemini_model()
+                .ok_or_else(|| anyhow::anyhow!("Gemini model is not set"))?;
+            Ok(Arc::new(Gemini::new(
+                api_key, prompt, model, structured, image, candidates,
+            )))
+        }
+        other => Err(anyhow::anyhow!("Unknown naming provider: {}", other)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    // fails with a connection error the first `failures` times
+    #[derive(Debug)]
+    struct Flaky {
+        name: &'static str,
+        failures: u32,
+        calls: AtomicU32,
+    }
+
+    impl Flaky {
+        fn wrap(name: &'static str, failures: u32) -> Arc<dyn NamingProvider> {
+            Arc::new(Self {
+                name,
+                failures,
+                calls: AtomicU32::new(0),
+            })
+        }
+    }
+
+    #[async_trait]
+    impl NamingProvider for Flaky {
+        fn id(&self) -> &'static str {
+            "flaky"
+        }
+
+        async fn suggest_name(&self, _image_path: &PathBuf) -> Result<Suggestion, ProviderError> {
+            if self.calls.fetch_add(1, Ordering::SeqCst) < self.failures {
+                return Err(ProviderError::Network("connection reset".to_string()));
+            }
+            Ok(Suggestion::new(self.name.to_string()))
+        }
+    }
+
+    fn fallback(failures: u32) -> Fallback {
+        let primary = Flaky::wrap("login-page", failures);
+        Fallback::new(
+            primary,
+            Flaky::wrap("offline-name", 0),
+            RetryPolicy::immediate(3),
+        )
+    }
+
+    #[tokio::test]
+    async fn retries_before_falling_back() {
+        let image = PathBuf::from("shot.png");
+        let name = fallback(2).suggest_name(&image).await.unwrap().name;
+        assert_eq!(name, "login-page");
+
+        let name = fallback(3).suggest_name(&image).await.unwrap().name;
+        assert_eq!(name, "offline-name");
+    }
+}
@@ -0,0 +1,194 @@
+use std::{fs, path::PathBuf, sync::Arc};
+
+use async_trait::async_trait;
+use log::{info, warn};
+use sha2::{Digest, Sha256};
+
+use crate::manager::{
+    error::ProviderError,
+    provider::{NamingProvider, Suggestion},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordMode {
+    // ask the real provider and store every answer
+    Record,
+    // only answer from stored recordings, never touch the network
+    Replay,
+}
+
+impl RecordMode {
+    pub fn parse(mode: &str) -> Option<Self> {
+        match mode.to_lowercase().as_str() {
+            "record" => Some(Self::Record),
+            "replay" => Some(Self::Replay),
+            _ => None,
+        }
+    }
+}
+
+// Stores real provider answers in a fixture directory and plays them back,
+// so runs against a recorded set of images are repeatable offline. Keyed
+// like the response cache, by image bytes and provider fingerprint, but
+// nothing expires and a missing recording in replay mode is an error.
+#[derive(Debug)]
+pub struct Recorder {
+    inner: Arc<dyn NamingProvider>,
+    dir: PathBuf,
+    mode: RecordMode,
+}
+
+impl Recorder {
+    pub fn wrap(
+        inner: Arc<dyn NamingProvider>,
+        dir: PathBuf,
+        mode: RecordMode,
+    ) -> Result<Arc<dyn NamingProvider>, anyhow::Error> {
+        fs::create_dir_all(&dir).map_err(|e| {
+            anyhow::anyhow!(
+                "Failed to create fixture directory: {:?}, Error: {}",
+                dir,
+                e
+            )
+        })?;
+        Ok(Arc::new(Self { inner, dir, mode }))
+    }
+
+    fn recording_path(&self, image_path: &PathBuf) -> Result<PathBuf, ProviderError> {
+        let mut hasher = Sha256::new();
+        hasher.update(fs::read(image_path)?);
+        hasher.update(self.inner.fingerprint(image_path).as_bytes());
+        Ok(self.dir.join(format!("{:x}.json", hasher.finalize())))
+    }
+}
+
+#[async_trait]
+impl NamingProvider for Recorder {
+    fn id(&self) -> &'static str {
+        self.inner.id()
+    }
+
+    fn model(&self) -> &str {
+        self.inner.model()
+    }
+
+    fn fingerprint(&self, image_path: &PathBuf) -> String {
+        self.inner.fingerprint(image_path)
+    }
+
+    async fn probe(&self) -> Result<(), anyhow::Error> {
+        match self.mode {
+            RecordMode::Record => self.inner.probe().await,
+            RecordMode::Replay => Ok(()),
+        }
+    }
+
+    async fn suggest_name(&self, image_path: &PathBuf) -> Result<Suggestion, ProviderError> {
+        let path = self.recording_path(image_path)?;
+
+        if self.mode == RecordMode::Replay {
+            let content = fs::read_to_string(&path).map_err(|_| {
+                ProviderError::NotRecorded(format!(
+                    "no recording for {:?} in {:?}",
+                    image_path, self.dir
+                ))
+            })?;
+            let mut suggestion: Suggestion = serde_json::from_str(&content)?;
+            info!("Replaying recorded name for {:?}", image_path.display());
+            // nothing was spent on a replay
+            suggestion.usage = None;
+            return Ok(suggestion);
+        }
+
+        let suggestion = self.inner.suggest_name(image_path).await?;
+        let written = serde_json::to_string_pretty(&suggestion)
+            .map_err(anyhow::Error::from)
+            .and_then(|json| Ok(fs::write(&path, json)?));
+        if let Err(e) = written {
+            warn!("Failed to record answer for {:?}: {}", image_path, e);
+        }
+        Ok(suggestion)
+    }
+
+    // depends on the files around the image, so nothing to record or replay
+    async fn suggest_distinct_name(
+        &self,
+        image_path: &PathBuf,
+        taken: &[String],
+    ) -> Result<Suggestion, ProviderError> {
+        match self.mode {
+            RecordMode::Record => self.inner.suggest_distinct_name(image_path, taken).await,
+            RecordMode::Replay => Err(ProviderError::NotRecorded(format!(
+                "no new names for {:?} in replay mode",
+                image_path
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::manager::{fake::Fake, image::SSManager, journal::Journal, retry::RetryPolicy};
+
+    use super::*;
+
+    // the rename flow runs on recorded answers only, the fake provider
+    // underneath would name the files differently
+    #[tokio::test]
+    async fn replays_recorded_renames() {
+        let dir = std::env::temp_dir().join(format!("peeksy-replay-{}", std::process::id()));
+        let fixtures = dir.join("fixtures");
+        fs::create_dir_all(&fixtures).unwrap();
+        let recorded = dir.join("Screenshot 1.png");
+        let missing = dir.join("Screenshot 2.png");
+        fs::write(&recorded, b"first image").unwrap();
+        fs::write(&missing, b"second image").unwrap();
+
+        let recorder = Recorder {
+            inner: Arc::new(Fake::new(HashMap::new(), 1)),
+            dir: fixtures,
+            mode: RecordMode::Replay,
+        };
+        let mut answer = Suggestion::new("Login Page".to_string());
+        answer.tags = vec!["login".to_string()];
+        fs::write(
+            recorder.recording_path(&recorded).unwrap(),
+            serde_json::to_string(&answer).unwrap(),
+        )
+        .unwrap();
+
+        let journal = Journal::at(dir.join("journal.jsonl"));
+        let ss_manager = SSManager::new(Arc::new(recorder), RetryPolicy::new(1))
+            .with_journal(journal.clone())
+            .with_run("replay-test".to_string());
+
+        let renamed = ss_manager
+            .process_random_image(&recorded)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(renamed.to, dir.join("login-page.png"));
+        assert_eq!(renamed.suggestion.tags, ["login"]);
+        assert!(!recorded.exists() && renamed.to.exists());
+
+        let entries = journal.entries();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].provider, "fake");
+        assert_eq!(entries[0].run.as_deref(), Some("replay-test"));
+
+        // a file without a recording fails on its own, the run goes on
+        let e = ss_manager.process_random_image(&missing).await.unwrap_err();
+        let e = e.downcast_ref::<ProviderError>().unwrap();
+        assert!(matches!(e, ProviderError::NotRecorded(_)), "{}", e);
+        assert!(!e.is_fatal());
+        assert!(missing.exists());
+
+        // and undo puts the recorded rename back
+        journal.undo(&entries[0]).unwrap();
+        assert!(recorded.exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,106 @@
+use std::{
+    future::Future,
+    path::PathBuf,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use log::warn;
+
+use crate::{config::config::Config, manager::error::ProviderError};
+
+const DEFAULT_MAX_ATTEMPTS: u32 = 4;
+const BASE_DELAY: Duration = Duration::from_secs(1);
+const MAX_DELAY: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    base_delay: Duration,
+    max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: DEFAULT_MAX_ATTEMPTS,
+            base_delay: BASE_DELAY,
+            max_delay: MAX_DELAY,
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn new(max_attempts: u32) -> Self {
+        Self {
+            max_attempts: max_attempts.max(1),
+            ..Default::default()
+        }
+    }
+
+    // retries without waiting
+    #[cfg(test)]
+    pub fn immediate(max_attempts: u32) -> Self {
+        Self {
+            max_attempts,
+            base_delay: Duration::ZERO,
+            max_delay: Duration::ZERO,
+        }
+    }
+
+    pub fn from_config(config: &Config) -> Self {
+        Self::new(config.get_max_attempts())
+    }
+
+    // rate limits wait for as long as the provider asked, everything else
+    // backs off exponentially with up to 50% jitter on top
+    fn delay(&self, attempt: u32, error: &ProviderError) -> Duration {
+        if let ProviderError::RateLimited {
+            retry_after: Some(retry_after),
+            ..
+        } = error
+        {
+            return (*retry_after).min(self.max_delay);
+        }
+
+        let backoff = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt - 1))
+            .min(self.max_delay);
+        backoff + jitter(backoff / 2)
+    }
+
+    pub async fn run<T, F, Fut>(&self, path: &PathBuf, mut request: F) -> Result<T, ProviderError>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, ProviderError>>,
+    {
+        let mut attempt = 1;
+        loop {
+            match request().await {
+                Err(e) if e.is_transient() && attempt < self.max_attempts => {
+                    let delay = self.delay(attempt, &e);
+                    warn!(
+                        "Attempt {}/{} for {:?} failed, retrying in {:?}: {}",
+                        attempt, self.max_attempts, path, delay, e
+                    );
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+// cheap jitter source, the sub second clock is random enough to spread
+// retries from parallel renames apart
+fn jitter(max: Duration) -> Duration {
+    if max.is_zero() {
+        return Duration::ZERO;
+    }
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|now| now.subsec_nanos() as u128)
+        .unwrap_or(0);
+    Duration::from_nanos((nanos % max.as_nanos()) as u64)
+}
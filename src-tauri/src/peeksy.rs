@@ -0,0 +1,26 @@
+// Entry point of the peeksy cli and daemon, built by peeksy/Cargo.toml. The
+// tauri app in lib.rs calls this binary for suggestions and renames.
+
+// the cli/cli.rs layout and &PathBuf arguments are used throughout
+#![allow(clippy::module_inception, clippy::ptr_arg)]
+
+mod cli;
+#[path = "configs/mod.rs"]
+mod config;
+mod daemon;
+mod launchd;
+mod logger;
+mod manager;
+mod utils;
+
+use clap::Parser;
+
+#[tokio::main]
+async fn main() {
+    logger::logger::setup_logger();
+    if let Err(e) = config::setup::initial_setup() {
+        eprintln!("Failed to set up peeksy config: {}", e);
+        std::process::exit(1);
+    }
+    cli::cli::Args::parse().execute().await;
+}
@@ -0,0 +1,91 @@
+use std::{process::Command, sync::Mutex};
+
+use serde::{Deserialize, Serialize};
+
+// A file from the finder selection waiting for the user to pick a name in
+// the app window
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PendingRename {
+    pub file: String,
+    pub candidates: Vec<String>,
+    // from structured output, handed to `rename` so it can embed them
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+#[derive(Debug, Default)]
+pub struct PendingRenames(pub Mutex<Vec<PendingRename>>);
+
+// asks the cli for candidate names without touching the file
+pub fn suggest(file: &str) -> Option<PendingRename> {
+    let output = Command::new("peeksy")
+        .arg("suggest")
+        .arg(file)
+        .output()
+        .ok()?;
+    let output_str = String::from_utf8_lossy(&output.stdout);
+    let line = output_str.lines().find(|line| line.starts_with('{'))?;
+    let suggestion: serde_json::Value = serde_json::from_str(line).ok()?;
+
+    let mut candidates: Vec<String> = vec![];
+    let names = std::iter::once(&suggestion["name"]).chain(
+        suggestion["alternatives"]
+            .as_array()
+            .map(|names| names.iter())
+            .unwrap_or_default(),
+    );
+    for name in names.filter_map(|name| name.as_str()) {
+        if !name.is_empty() && !candidates.iter().any(|c| c == name) {
+            candidates.push(name.to_string());
+        }
+    }
+
+    if candidates.is_empty() {
+        return None;
+    }
+    Some(PendingRename {
+        file: file.to_string(),
+        candidates,
+        description: suggestion["description"].as_str().map(|d| d.to_string()),
+        tags: suggestion["tags"]
+            .as_array()
+            .map(|tags| {
+                tags.iter()
+                    .filter_map(|tag| tag.as_str().map(|tag| tag.to_string()))
+                    .collect()
+            })
+            .unwrap_or_default(),
+    })
+}
+
+// commits the chosen name through the cli, with the description and tags
+// of the suggestion for the metadata
+pub fn apply(pending: &PendingRename, name: &str) -> bool {
+    let file = &pending.file;
+    let mut command = Command::new("peeksy");
+    command.arg("rename").arg(file).arg("--name").arg(name);
+    if let Some(description) = pending.description.as_ref() {
+        command.arg("--description").arg(description);
+    }
+    for tag in pending.tags.iter() {
+        command.arg("--tag").arg(tag);
+    }
+
+    let output = match command.output() {
+        Ok(output) => output,
+        Err(e) => {
+            eprintln!("Failed to run peeksy rename for {}: {}", file, e);
+            return false;
+        }
+    };
+    if !output.status.success() {
+        eprintln!(
+            "Failed to rename {}: {}",
+            file,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    output.status.success()
+}
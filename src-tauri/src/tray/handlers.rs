@@ -1,11 +1,12 @@
-use std::process::Command;
-
 use rayon::prelude::*;
-use tauri::{AppHandle, Manager, WebviewUrl, WebviewWindowBuilder};
+use tauri::{AppHandle, Emitter, Manager, WebviewUrl, WebviewWindowBuilder};
 use tauri_plugin_positioner::{Position, WindowExt};
 use window_vibrancy::{apply_blur, apply_vibrancy, NSVisualEffectMaterial, NSVisualEffectState};
 
-use crate::files;
+use crate::{
+    files,
+    rename::{self, PendingRename, PendingRenames},
+};
 
 pub fn menue_item_config_handler(app: &AppHandle) {
     if let Some(window) = app.get_webview_window("main") {
@@ -43,20 +44,37 @@ pub fn menue_item_config_handler(app: &AppHandle) {
     }
 }
 
-pub fn menue_item_rename_handler(_: &AppHandle) {
+pub fn menue_item_rename_handler(app: &AppHandle) {
     let files = files::macos::get_finder_selection();
     println!("files: {:?}", files);
 
     match files {
         Some(files) => {
-            files.par_iter().for_each(|file| {
-                let cmd = Command::new("peeksy")
-                    .arg("rename")
-                    .arg(file)
-                    .output()
-                    .expect("Failed to rename files");
-                println!("cmd: {:?}", cmd);
-            });
+            let suggestions: Vec<PendingRename> = files
+                .par_iter()
+                .filter_map(|file| rename::suggest(file))
+                .collect();
+
+            // a single candidate leaves nothing to choose, rename right away
+            let (mut pending, single): (Vec<PendingRename>, Vec<PendingRename>) = suggestions
+                .into_iter()
+                .partition(|suggestion| suggestion.candidates.len() > 1);
+            // the ones that failed are offered in the window instead
+            let failed: Vec<PendingRename> = single
+                .into_par_iter()
+                .filter(|suggestion| !rename::apply(suggestion, &suggestion.candidates[0]))
+                .collect();
+            pending.extend(failed);
+
+            if !pending.is_empty() {
+                let state = app.state::<PendingRenames>();
+                let mut renames = state.0.lock().unwrap();
+                renames.retain(|rename| !pending.iter().any(|p| p.file == rename.file));
+                renames.extend(pending);
+
+                menue_item_config_handler(app);
+                let _ = app.emit("pending-renames", renames.clone());
+            }
         }
         None => {
             println!("No files selected");
@@ -0,0 +1,182 @@
+use crate::utils::time;
+
+// leaves room for the extension and a collision suffix within the 255 byte
+// limit of apfs and most other filesystems
+const MAX_NAME_BYTES: usize = 100;
+
+const RESERVED_NAMES: [&str; 4] = ["con", "prn", "aux", "nul"];
+
+const EXTENSIONS: [&str; 10] = [
+    ".png", ".jpg", ".jpeg", ".gif", ".webp", ".heic", ".heif", ".bmp", ".tiff", ".tif",
+];
+
+// Turns raw model output into a safe file stem: only [a-z0-9-], no leading,
+// trailing or repeated hyphens, at most MAX_NAME_BYTES long and never a
+// reserved name. Falls back to a timestamp name when nothing usable is left.
+pub fn sanitize(raw: &str) -> String {
+    let name = clean(raw);
+    if name.is_empty() || is_reserved(&name) {
+        return timestamp_name();
+    }
+    name
+}
+
+fn clean(raw: &str) -> String {
+    // models like to wrap the answer in code fences or add a second line
+    // of explanation, the first real line is the name
+    let line = raw
+        .lines()
+        .map(|line| line.trim())
+        .find(|line| !line.is_empty() && !line.starts_with("```"))
+        .unwrap_or("");
+
+    let mut line = line
+        .trim_matches(|c: char| c == '`' || c == '"' || c == '\'' || c.is_whitespace())
+        .to_lowercase();
+
+    for extension in EXTENSIONS {
+        if let Some(stem) = line.strip_suffix(extension) {
+            line = stem.to_string();
+            break;
+        }
+    }
+
+    let mut name = String::with_capacity(line.len());
+    for c in line.chars() {
+        let c = if c.is_ascii_lowercase() || c.is_ascii_digit() {
+            c
+        } else {
+            '-'
+        };
+        if c == '-' && (name.is_empty() || name.ends_with('-')) {
+            continue;
+        }
+        name.push(c);
+    }
+
+    truncate(name.trim_end_matches('-'))
+}
+
+// cuts at the last hyphen that fits so words are not chopped in half,
+// everything is ascii at this point so byte and char boundaries agree
+fn truncate(name: &str) -> String {
+    if name.len() <= MAX_NAME_BYTES {
+        return name.to_string();
+    }
+
+    let cut = &name[..MAX_NAME_BYTES];
+    match cut.rfind('-') {
+        Some(index) if index > 0 => cut[..index].to_string(),
+        _ => cut.to_string(),
+    }
+}
+
+fn is_reserved(name: &str) -> bool {
+    if RESERVED_NAMES.contains(&name) {
+        return true;
+    }
+
+    // com1..com9 and lpt1..lpt9
+    let bytes = name.as_bytes();
+    bytes.len() == 4
+        && (name.starts_with("com") || name.starts_with("lpt"))
+        && (b'1'..=b'9').contains(&bytes[3])
+}
+
+// sanitized names are kebab case, snake style swaps the separators
+pub fn styled(name: &str, style: &str) -> String {
+    match style {
+        "snake" => name.replace('-', "_"),
+        _ => name.to_string(),
+    }
+}
+
+// peeksy-20250131-142501, in utc
+pub fn timestamp_name() -> String {
+    format!("peeksy-{}", time::compact(time::unix_now()))
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    fn is_timestamp_name(name: &str) -> bool {
+        let digits = name.strip_prefix("peeksy-").unwrap_or_default();
+        digits.len() == 15
+            && digits.chars().enumerate().all(|(index, c)| {
+                if index == 8 {
+                    c == '-'
+                } else {
+                    c.is_ascii_digit()
+                }
+            })
+    }
+
+    fn assert_safe(name: &str) {
+        assert!(name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-'));
+        assert!(!name.starts_with('-') && !name.ends_with('-'));
+        assert!(!name.contains("--"));
+        assert!(!name.is_empty() && name.len() <= MAX_NAME_BYTES);
+        assert!(!is_reserved(name));
+        assert!(!name.contains('/') && !name.contains(".."));
+    }
+
+    proptest! {
+        #[test]
+        fn sanitize_is_safe_for_any_input(raw in any::<String>()) {
+            assert_safe(&sanitize(&raw));
+        }
+
+        #[test]
+        fn sanitize_is_safe_for_long_names(raw in "[a-zA-Z0-9 ./_-]{90,400}") {
+            assert_safe(&sanitize(&raw));
+        }
+
+        #[test]
+        fn sanitize_avoids_reserved_names(
+            raw in "(```\n)?[`\"' ]{0,2}(CON|con|Prn|aux|NUL|com[0-9]|LPT[0-9])(\\.png|\\.jpg)?[`\"' ]{0,2}"
+        ) {
+            assert_safe(&sanitize(&raw));
+        }
+
+        #[test]
+        fn sanitize_falls_back_when_nothing_is_left(raw in "[ \n`\"'./_!?]{0,40}") {
+            prop_assert!(clean(&raw).is_empty());
+            prop_assert!(is_timestamp_name(&sanitize(&raw)));
+        }
+
+        #[test]
+        fn sanitize_keeps_clean_names(name in "[a-z0-9]{1,10}(-[a-z0-9]{1,10}){0,5}") {
+            prop_assume!(!is_reserved(&name));
+            prop_assert_eq!(sanitize(&name), name);
+        }
+    }
+
+    #[test]
+    fn reserved_names() {
+        for name in ["con", "prn", "aux", "nul", "com1", "com9", "lpt1", "lpt9"] {
+            assert!(is_reserved(name), "{}", name);
+            assert!(is_timestamp_name(&sanitize(name)), "{}", name);
+        }
+        for name in ["com0", "lpt10", "console", "comet"] {
+            assert!(!is_reserved(name), "{}", name);
+        }
+    }
+
+    #[test]
+    fn truncate_cuts_at_a_hyphen() {
+        let name = format!("{}-tail", "a".repeat(MAX_NAME_BYTES - 2));
+        assert_eq!(truncate(&name), "a".repeat(MAX_NAME_BYTES - 2));
+        assert_eq!(truncate(&"b".repeat(200)), "b".repeat(MAX_NAME_BYTES));
+    }
+
+    #[test]
+    fn clean_strips_fences_quotes_and_extensions() {
+        assert_eq!(clean("```\n\"Login Screen.PNG\"\n```"), "login-screen");
+        assert_eq!(clean("  ../../etc/passwd  "), "etc-passwd");
+    }
+}
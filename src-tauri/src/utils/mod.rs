@@ -1 +1,4 @@
+pub mod filename;
+pub mod mover;
 pub mod ss;
+pub mod time;
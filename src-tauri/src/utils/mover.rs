@@ -0,0 +1,371 @@
+use std::{
+    fs::{self, File, FileTimes},
+    io,
+    path::{Path, PathBuf},
+};
+
+use log::{info, warn};
+
+// Moves a file without ever replacing an existing one. The target is
+// claimed with a hard link, which fails atomically when the name is taken,
+// also when it only differs in case on a case-insensitive volume. The link
+// keeps the inode, so creation time, permissions and extended attributes
+// stay as they are. Across filesystems the target is claimed with an empty
+// file, the source is copied next to it, synced, given the source's
+// timestamps and xattrs, and only then put in place and the source removed.
+// If any step fails the source is left untouched and nothing is left behind
+// at the target. A taken target is reported as `ErrorKind::AlreadyExists`,
+// see `is_taken`.
+pub fn move_file(from: &Path, to: &Path) -> Result<(), anyhow::Error> {
+    // only the case changes, the target is the source itself
+    if is_same_file(from, to) {
+        return fs::rename(from, to).map_err(|e| {
+            anyhow::anyhow!(
+                "Failed to rename file: {:?} -> {:?}, Error: {}",
+                from,
+                to,
+                e
+            )
+        });
+    }
+
+    match fs::hard_link(from, to) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => return Err(taken(to)),
+        Err(e) if e.raw_os_error() == Some(libc::EXDEV) => {
+            info!(
+                "{:?} and {:?} are on different filesystems, copying",
+                from, to
+            );
+            return move_across(from, to);
+        }
+        // volumes without hard links, e.g. exfat
+        Err(e) if no_hard_links(&e) => return move_reserved(from, to),
+        Err(e) => {
+            return Err(anyhow::anyhow!(
+                "Failed to rename file: {:?} -> {:?}, Error: {}",
+                from,
+                to,
+                e
+            ))
+        }
+    }
+
+    if let Err(e) = fs::remove_file(from) {
+        // keep one name, not two
+        discard(to);
+        return Err(anyhow::anyhow!(
+            "Failed to delete file: {:?}, Error: {}",
+            from,
+            e
+        ));
+    }
+    Ok(())
+}
+
+// linux answers EPERM where the filesystem can't link, macos ENOTSUP,
+// EMLINK means the source has too many names already
+fn no_hard_links(error: &io::Error) -> bool {
+    // ENOTSUP and EOPNOTSUPP are the same on linux but not on macos
+    error.raw_os_error().is_some_and(|code| {
+        [libc::EPERM, libc::ENOTSUP, libc::EOPNOTSUPP, libc::EMLINK].contains(&code)
+    })
+}
+
+// whether an error from `move_file` means the target name is already in use
+pub fn is_taken(error: &anyhow::Error) -> bool {
+    error
+        .downcast_ref::<io::Error>()
+        .is_some_and(|e| e.kind() == io::ErrorKind::AlreadyExists)
+}
+
+// the error `move_file` reports for a taken target
+pub fn taken(to: &Path) -> anyhow::Error {
+    io::Error::new(
+        io::ErrorKind::AlreadyExists,
+        format!("{:?} already exists", to),
+    )
+    .into()
+}
+
+fn is_same_file(a: &Path, b: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    match (fs::metadata(a), fs::metadata(b)) {
+        (Ok(a), Ok(b)) => a.dev() == b.dev() && a.ino() == b.ino(),
+        _ => false,
+    }
+}
+
+// whether two paths name the same entry on a case-insensitive volume
+pub fn same_name(a: &Path, b: &Path) -> bool {
+    a.parent() == b.parent()
+        && match (a.file_name(), b.file_name()) {
+            (Some(a), Some(b)) => {
+                a.to_string_lossy().to_lowercase() == b.to_string_lossy().to_lowercase()
+            }
+            _ => false,
+        }
+}
+
+// Whether `move_file` would find the target free, without claiming it. A
+// name that only differs in case counts as taken unless it is `from`
+// itself. Only a hint, by the time the move runs the answer may be stale.
+pub fn is_free(from: &Path, to: &Path) -> bool {
+    if is_same_file(from, to) {
+        return true;
+    }
+    let parent = to.parent().unwrap_or(Path::new("."));
+    let entries = match fs::read_dir(parent) {
+        Ok(entries) => entries,
+        Err(_) => return !to.exists(),
+    };
+    !entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| parent.join(entry.file_name()))
+        .any(|existing| existing != from && same_name(&existing, to))
+}
+
+// an empty file under the target name, created only if nothing is there
+fn reserve(to: &Path) -> Result<(), anyhow::Error> {
+    match File::options().write(true).create_new(true).open(to) {
+        Ok(_) => Ok(()),
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => Err(taken(to)),
+        Err(e) => Err(anyhow::anyhow!(
+            "Failed to create file: {:?}, Error: {}",
+            to,
+            e
+        )),
+    }
+}
+
+// the target is ours once reserved, so renaming over it replaces nothing
+fn move_reserved(from: &Path, to: &Path) -> Result<(), anyhow::Error> {
+    reserve(to)?;
+    if let Err(e) = fs::rename(from, to) {
+        discard(to);
+        return Err(anyhow::anyhow!(
+            "Failed to rename file: {:?} -> {:?}, Error: {}",
+            from,
+            to,
+            e
+        ));
+    }
+    Ok(())
+}
+
+fn move_across(from: &Path, to: &Path) -> Result<(), anyhow::Error> {
+    reserve(to)?;
+
+    let staging = staging_path(to);
+    if let Err(e) = stage(from, &staging) {
+        discard(&staging);
+        discard(to);
+        return Err(anyhow::anyhow!(
+            "Failed to copy file: {:?} -> {:?}, Error: {}",
+            from,
+            to,
+            e
+        ));
+    }
+
+    // same directory, so this rename is atomic again
+    if let Err(e) = fs::rename(&staging, to) {
+        discard(&staging);
+        discard(to);
+        return Err(anyhow::anyhow!(
+            "Failed to move file into place: {:?}, Error: {}",
+            to,
+            e
+        ));
+    }
+    sync_dir(to);
+
+    if let Err(e) = fs::remove_file(from) {
+        // keep one copy, not two
+        discard(to);
+        return Err(anyhow::anyhow!(
+            "Failed to delete file: {:?}, Error: {}",
+            from,
+            e
+        ));
+    }
+    Ok(())
+}
+
+// a hidden file next to the target, so a crash never leaves a half written
+// file under the final name
+fn staging_path(to: &Path) -> PathBuf {
+    let name = to
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or_default();
+    to.with_file_name(format!(".{}.peeksy-tmp", name))
+}
+
+fn stage(from: &Path, staging: &Path) -> Result<(), io::Error> {
+    // copies the contents and permissions
+    fs::copy(from, staging)?;
+    finish_staging(from, staging)
+}
+
+// syncs the staged copy and gives it the timestamps and xattrs of `from`
+fn finish_staging(from: &Path, staging: &Path) -> Result<(), io::Error> {
+    let metadata = fs::metadata(from)?;
+    let file = File::options().write(true).open(staging)?;
+    file.sync_all()?;
+
+    let times = FileTimes::new()
+        .set_accessed(metadata.accessed()?)
+        .set_modified(metadata.modified()?);
+    #[cfg(target_os = "macos")]
+    let times = {
+        use std::os::macos::fs::FileTimesExt;
+        match metadata.created() {
+            Ok(created) => times.set_created(created),
+            Err(_) => times,
+        }
+    };
+    file.set_times(times)?;
+
+    copy_xattrs(from, staging)
+}
+
+// Swaps the contents of a file in one step, e.g. after adding metadata.
+// The new bytes are staged next to it with the old permissions, timestamps
+// and xattrs and renamed over it, so readers see either version in full.
+pub fn replace_contents(path: &Path, bytes: &[u8]) -> Result<(), anyhow::Error> {
+    let staging = staging_path(path);
+    let staged = fs::write(&staging, bytes)
+        .and_then(|_| fs::set_permissions(&staging, fs::metadata(path)?.permissions()))
+        .and_then(|_| finish_staging(path, &staging))
+        .and_then(|_| fs::rename(&staging, path));
+    if let Err(e) = staged {
+        discard(&staging);
+        return Err(anyhow::anyhow!(
+            "Failed to rewrite file: {:?}, Error: {}",
+            path,
+            e
+        ));
+    }
+    sync_dir(path);
+    Ok(())
+}
+
+fn copy_xattrs(from: &Path, to: &Path) -> Result<(), io::Error> {
+    for name in xattr::list(from)? {
+        if let Some(value) = xattr::get(from, &name)? {
+            xattr::set(to, &name, &value)?;
+        }
+    }
+    Ok(())
+}
+
+// makes the new directory entry durable, failing here is not worth a rollback
+fn sync_dir(path: &Path) {
+    let parent = path.parent().unwrap_or(Path::new("."));
+    if let Err(e) = File::open(parent).and_then(|dir| dir.sync_all()) {
+        warn!("Failed to sync directory: {:?}, Error: {}", parent, e);
+    }
+}
+
+fn discard(path: &Path) {
+    if let Err(e) = fs::remove_file(path) {
+        if e.kind() != io::ErrorKind::NotFound {
+            warn!("Failed to clean up: {:?}, Error: {}", path, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("peeksy-mover-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn names(dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn never_replaces_existing_target() {
+        let dir = temp_dir("taken");
+        let (from, to) = (dir.join("shot.png"), dir.join("login-page.png"));
+        fs::write(&from, b"new").unwrap();
+        fs::write(&to, b"existing").unwrap();
+
+        let e = move_file(&from, &to).unwrap_err();
+        assert!(is_taken(&e));
+        assert_eq!(fs::read(&from).unwrap(), b"new");
+        assert_eq!(fs::read(&to).unwrap(), b"existing");
+
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn moves_to_free_target() {
+        let dir = temp_dir("free");
+        let (from, to) = (dir.join("shot.png"), dir.join("login-page.png"));
+        fs::write(&from, b"new").unwrap();
+
+        move_file(&from, &to).unwrap();
+        assert_eq!(names(&dir), ["login-page.png"]);
+        assert_eq!(fs::read(&to).unwrap(), b"new");
+
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    // on a case-insensitive volume both names are the same entry, on a
+    // case-sensitive one the move is an ordinary one
+    #[test]
+    fn case_only_rename() {
+        let dir = temp_dir("case");
+        let (from, to) = (dir.join("login-page.png"), dir.join("Login-Page.png"));
+        fs::write(&from, b"new").unwrap();
+
+        assert!(is_free(&from, &to));
+        move_file(&from, &to).unwrap();
+        assert_eq!(names(&dir), ["Login-Page.png"]);
+        assert_eq!(fs::read(&to).unwrap(), b"new");
+
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn other_file_with_same_name_in_other_case_is_taken() {
+        let dir = temp_dir("other-case");
+        let (from, existing) = (dir.join("shot.png"), dir.join("Login-Page.png"));
+        fs::write(&from, b"new").unwrap();
+        fs::write(&existing, b"existing").unwrap();
+
+        let to = dir.join("login-page.png");
+        assert!(same_name(&existing, &to));
+        assert!(!same_name(&existing, &dir.join("login-page-2.png")));
+        assert!(!is_free(&from, &to));
+        assert!(is_free(&from, &dir.join("login-page-2.png")));
+
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn missing_source_is_an_error() {
+        let dir = std::env::temp_dir().join(format!("peeksy-mover-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let to = dir.join("to.png");
+
+        let e = move_file(&dir.join("from.png"), &to).unwrap_err();
+        assert!(!is_taken(&e));
+        assert!(!to.exists());
+
+        fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -4,25 +4,32 @@ use anyhow::Error;
 use log::error;
 
 fn get_clean_path(raw: String, home: PathBuf) -> Result<PathBuf, Error> {
-    if raw.starts_with("~/") {
-        return Ok(home.join(raw[2..].to_string()));
+    if let Some(relative) = raw.strip_prefix("~/") {
+        return Ok(home.join(relative));
     }
 
     let raw_path = PathBuf::from(raw.clone());
     // if raw_path has home path, then return raw_path
     if raw_path.starts_with(&home) {
-        return Ok(raw_path);
+        Ok(raw_path)
     } else {
-        return Err(anyhow::anyhow!(
+        Err(anyhow::anyhow!(
             "Raw path {} is not understanding",
             raw_path.display()
-        ));
+        ))
     }
 }
 
 pub fn get_screenshot_dir() -> PathBuf {
     use std::process::Command;
 
+    // lets end-to-end runs point the daemon and bulk processing at a temp dir
+    if let Ok(dir) = std::env::var("PEEKSY_SCREENSHOT_DIR") {
+        if !dir.is_empty() {
+            return PathBuf::from(dir);
+        }
+    }
+
     // picks desktop dir if default is not found
     let desktop_ss_dir = dirs::desktop_dir().unwrap_or_else(|| PathBuf::from("/Users/Shared"));
 
@@ -0,0 +1,152 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// all dates are utc, peeksy has no timezone database to lean on
+
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|now| now.as_secs())
+        .unwrap_or(0)
+}
+
+// days since 1970-01-01 to (year, month, day), from Howard Hinnant's
+// chrono-compatible date algorithms
+pub fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+// 2025-01-31
+pub fn date(secs: u64) -> String {
+    let (year, month, day) = civil_from_days((secs / 86_400) as i64);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+// checks a 2025-01-31 day given on the command line, so it compares
+// correctly against `date`
+pub fn parse_date(value: &str) -> Result<String, String> {
+    let invalid = || format!("expected a date like 2025-01-31, got {:?}", value);
+    let parts: Vec<&str> = value.split('-').collect();
+    let [year, month, day] = parts.as_slice() else {
+        return Err(invalid());
+    };
+    let digits = |part: &str, len: usize| {
+        (part.len() == len && part.chars().all(|c| c.is_ascii_digit()))
+            .then(|| part.parse::<u32>().ok())
+            .flatten()
+    };
+    let (Some(year), Some(month), Some(day)) = (digits(year, 4), digits(month, 2), digits(day, 2))
+    else {
+        return Err(invalid());
+    };
+
+    let leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+    let days_in_month = match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 if leap => 29,
+        2 => 28,
+        _ => return Err(invalid()),
+    };
+    if day == 0 || day > days_in_month {
+        return Err(invalid());
+    }
+    Ok(value.to_string())
+}
+
+// 2025-01
+pub fn month(secs: u64) -> String {
+    date(secs)[..7].to_string()
+}
+
+// 20250131-142501
+pub fn compact(secs: u64) -> String {
+    let (year, month, day) = civil_from_days((secs / 86_400) as i64);
+    let rem = secs % 86_400;
+    format!(
+        "{:04}{:02}{:02}-{:02}{:02}{:02}",
+        year,
+        month,
+        day,
+        rem / 3600,
+        (rem % 3600) / 60,
+        rem % 60
+    )
+}
+
+// 2025-01-31 14:25:01
+pub fn datetime(secs: u64) -> String {
+    let rem = secs % 86_400;
+    format!(
+        "{} {:02}:{:02}:{:02}",
+        date(secs),
+        rem / 3600,
+        (rem % 3600) / 60,
+        rem % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn civil_from_days_matches_known_dates() {
+        let known = [
+            (0, (1970, 1, 1)),
+            (-1, (1969, 12, 31)),
+            (11_016, (2000, 2, 29)),
+            (11_017, (2000, 3, 1)),
+            (19_782, (2024, 2, 29)),
+            (20_119, (2025, 1, 31)),
+            (-25_508, (1900, 3, 1)),
+            (47_846, (2100, 12, 31)),
+        ];
+        for (days, date) in known {
+            assert_eq!(civil_from_days(days), date, "day {}", days);
+        }
+    }
+
+    #[test]
+    fn formats_utc_timestamps() {
+        // 2025-01-31 14:25:01
+        let secs = 20_119 * 86_400 + 14 * 3600 + 25 * 60 + 1;
+        assert_eq!(date(secs), "2025-01-31");
+        assert_eq!(month(secs), "2025-01");
+        assert_eq!(compact(secs), "20250131-142501");
+        assert_eq!(datetime(secs), "2025-01-31 14:25:01");
+    }
+
+    #[test]
+    fn parses_only_full_dates() {
+        for valid in ["2025-01-31", "2024-02-29", "2000-02-29", "1999-12-01"] {
+            assert_eq!(parse_date(valid), Ok(valid.to_string()));
+        }
+        for invalid in [
+            "2025-1-5",
+            "2025-01-5",
+            "25-01-05",
+            "2025/01/05",
+            "2025-01",
+            "2025-01-05-01",
+            "2025-13-01",
+            "2025-00-10",
+            "2025-04-31",
+            "2025-02-29",
+            "1900-02-29",
+            "2025-01-00",
+            "+202-01-05",
+            "",
+        ] {
+            assert!(parse_date(invalid).is_err(), "{}", invalid);
+        }
+    }
+}
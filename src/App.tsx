@@ -2,6 +2,7 @@ import "./App.css";
 import Navbar from "./components/navbar";
 import Configs from "./components/configs";
 import Header from "./components/header";
+import Candidates from "./components/candidates";
 
 function App() {
   return (
@@ -9,6 +10,7 @@ function App() {
       <div className="flex flex-col gap-3 p-4">
         <Navbar />
         <Header />
+        <Candidates />
         <Configs />
       </div>
     </div>
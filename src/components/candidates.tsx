@@ -0,0 +1,78 @@
+import { invoke } from "@tauri-apps/api/core";
+import { listen } from "@tauri-apps/api/event";
+import { useEffect, useState } from "react";
+
+export type PendingRename = {
+  file: string;
+  candidates: string[];
+  description: string | null;
+  tags: string[];
+};
+
+const fileName = (path: string) => path.split("/").pop() ?? path;
+
+const Candidates = () => {
+  let [pending, setPending] = useState<PendingRename[]>([]);
+
+  useEffect(() => {
+    invoke<PendingRename[]>("get_pending_renames").then(setPending);
+    const unlisten = listen<PendingRename[]>("pending-renames", (event) => {
+      setPending(event.payload);
+    });
+    return () => {
+      unlisten.then((unlisten) => unlisten());
+    };
+  }, []);
+
+  function pick(file: string, name: string) {
+    invoke<boolean>("apply_rename", { file, name }).then((renamed) => {
+      // a failed rename stays in the list to retry or keep
+      if (!renamed) {
+        console.error("failed to rename", file);
+        return;
+      }
+      setPending((pending) => pending.filter((p) => p.file !== file));
+    });
+  }
+
+  function skip(file: string) {
+    invoke("skip_rename", { file }).then(() => {
+      setPending((pending) => pending.filter((p) => p.file !== file));
+    });
+  }
+
+  if (pending.length === 0) {
+    return null;
+  }
+
+  return (
+    <div className="flex flex-col gap-4">
+      {pending.map((rename) => (
+        <div key={rename.file} className="flex flex-col gap-2">
+          <label className="text-sm font-medium text-gray-800 dark:text-gray-300">
+            {fileName(rename.file)}
+          </label>
+          <div className="flex flex-wrap gap-2">
+            {rename.candidates.map((name) => (
+              <button
+                key={name}
+                className="border border-gray-400 dark:border-gray-700 rounded-md px-2 py-1 text-sm hover:ring-2 hover:ring-blue-500 cursor-pointer"
+                onClick={() => pick(rename.file, name)}
+              >
+                {name}
+              </button>
+            ))}
+            <button
+              className="font-semibold text-white bg-red-500 rounded-md px-2 text-sm cursor-pointer"
+              onClick={() => skip(rename.file)}
+            >
+              keep
+            </button>
+          </div>
+        </div>
+      ))}
+    </div>
+  );
+};
+
+export default Candidates;
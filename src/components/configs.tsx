@@ -6,6 +6,7 @@ export type Config = {
   openai_api_key: string | null;
   openai_prompt_file_path: string | null;
   openai_model: string | null;
+  provider: string | null;
 };
 
 const Configs = () => {
@@ -21,6 +22,12 @@ const Configs = () => {
   return (
     <div>
       <div className="flex flex-col gap-4">
+        <ConfigsItem
+          label="Provider"
+          value={config?.provider ?? "openai"}
+          placeholder="openai, anthropic, gemini, ocr or fake"
+          disabled={true}
+        />
         <ConfigsItem
           label="OpenAI API Key"
           value={config?.openai_api_key ?? ""}